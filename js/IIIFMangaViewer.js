@@ -6,10 +6,21 @@ import init, {
     SearchQuery,
     SearchResult,
     SearchResults,
+    SearchHistory,
+    SearchView,
+    WasmSearchProvider,
+    FederatedSearch,
+    SyncGroup,
+    SyncMode,
+    CompareMode,
     CurationItem,
     WasmCurationViewer,
 } from '../pkg/iiif_manga_viewer_frontend.js';
 
+// Europeana Search APIのキー
+// [参考](https://pro.europeana.eu/page/get-api)
+const EUROPEANA_KEY = 'api2demo';
+
 async function run() {
     await init();
 
@@ -19,7 +30,29 @@ async function run() {
         viewers.appendChild(viewer);
     };
 
+    let openResult = (result) => {
+        const viewers = document.getElementById('viewers');
+        let viewer = new IIIFMangaViewer(null, result);
+        viewers.appendChild(viewer);
+    };
+
+    /**
+     * 文字列をHTMLとして解釈せずにtoastで表示する
+     * @param text {string}
+     */
+    let toastText = (text) => {
+        const span = document.createElement('span');
+        span.textContent = text;
+        M.toast({html: span});
+    };
+
     let viewerCounter = 0;
+    // 表示する頁の前後に先に読み込んでおく頁数
+    const PRELOAD_PAGES = 2;
+    // 同期できるビューア(syncId -> IIIFMangaViewer)
+    const mangaViewers = new Map();
+    // 頁送り、移動、拡大縮小を揃えるビューアの組
+    const syncGroup = new SyncGroup(SyncMode.Relative);
 
     /**
      * ビューアのIconViewのicon要素
@@ -27,14 +60,6 @@ async function run() {
     class IconViewItem extends HTMLElement {
         constructor() {
             super();
-
-            this.addEventListener('click', () => {
-                const src = this.getAttribute('src');
-                // 表示
-                this.mangaViewer.show(this.mangaViewer.viewer.get_index_by_src(src));
-                // メニュー非表示
-                this.iconView.onOff();
-            });
         }
 
         /**
@@ -119,6 +144,8 @@ async function run() {
             const a = this.mangaViewer.iconViewIcon;
             if (!this.classList.contains('hide')) {
                 a.classList.add('available');
+                // 見える範囲の項目を作り直す
+                this.mangaViewer.viewer.render_views();
             } else {
                 a.classList.remove('available');
             }
@@ -154,6 +181,80 @@ async function run() {
 
     customElements.define('icon-view', IconView);
 
+    /**
+     * Manifestの情報を表示するView
+     */
+    class MetadataView extends HTMLElement {
+        constructor() {
+            super();
+        }
+
+        onOff() {
+            this.classList.toggle('hide');
+
+            const a = this.mangaViewer.metadataViewIcon;
+            if (!this.classList.contains('hide')) {
+                a.classList.add('available');
+            } else {
+                a.classList.remove('available');
+            }
+        }
+
+        /**
+         * 要素が DOM に挿入されるたびに呼び出されます。
+         */
+        connectedCallback() {
+            this.classList.add('hide');
+            // 自分の所属するマンガビューアを登録しておく
+            let mangaViewer = this;
+            while (!(mangaViewer instanceof IIIFMangaViewer)) {
+                mangaViewer = mangaViewer.parentElement;
+                if (!mangaViewer) return;
+            }
+            this.mangaViewer = mangaViewer;
+        }
+    }
+
+    customElements.define('metadata-view', MetadataView);
+
+    /**
+     * 画像の下に並べる一覧とscrubber
+     */
+    class FilmstripView extends HTMLElement {
+        constructor() {
+            super();
+        }
+
+        onOff() {
+            this.classList.toggle('hide');
+
+            const a = this.mangaViewer.filmstripViewIcon;
+            if (!this.classList.contains('hide')) {
+                a.classList.add('available');
+                // 見える範囲の項目を作り直す
+                this.mangaViewer.viewer.render_views();
+            } else {
+                a.classList.remove('available');
+            }
+        }
+
+        /**
+         * 要素が DOM に挿入されるたびに呼び出されます。
+         */
+        connectedCallback() {
+            this.classList.add('hide');
+            // 自分の所属するマンガビューアを登録しておく
+            let mangaViewer = this;
+            while (!(mangaViewer instanceof IIIFMangaViewer)) {
+                mangaViewer = mangaViewer.parentElement;
+                if (!mangaViewer) return;
+            }
+            this.mangaViewer = mangaViewer;
+        }
+    }
+
+    customElements.define('filmstrip-view', FilmstripView);
+
     /**
      * ビューアのListViewのli要素
      */
@@ -163,17 +264,6 @@ async function run() {
 
             // 必要なclassを追加
             this.classList.add('collection-item', 'image-list-item');
-
-            // onclickを設定: 表示
-            this.onclick = () => {
-                const src = this.getAttribute('src');
-                // 表示
-                this.mangaViewer.show(this.mangaViewer.viewer.get_index_by_src(src));
-                // deactivate
-                this.imageList.deactivate();
-                // activate
-                this.classList.toggle('active');
-            }
         }
 
         loading() {
@@ -212,6 +302,13 @@ async function run() {
             const i = document.createElement('i');
             i.classList.add('status-icon', 'right');
             this.appendChild(i);
+
+            // 見える範囲に入るたびに作られるので、読み込み状態を引き継ぐ
+            if (listView.isLoaded(Number(this.getAttribute('data-index')))) {
+                this.loaded();
+            } else {
+                this.loading();
+            }
         }
     }
 
@@ -226,6 +323,9 @@ async function run() {
 
             // 必要なclassを追加
             this.classList.add('collection', 'with-header', 'image-list');
+
+            // 読み込み済みの画像の番号
+            this.loadedIndices = new Set();
         }
 
         onOff() {
@@ -234,11 +334,36 @@ async function run() {
             const a = this.mangaViewer.listViewIcon;
             if (!this.classList.contains('hide')) {
                 a.classList.add('available');
+                // 見える範囲の項目を作り直す
+                this.mangaViewer.viewer.render_views();
             } else {
                 a.classList.remove('available');
             }
         }
 
+        /**
+         * 画像を読み込み済みにする
+         * @param index
+         */
+        markLoaded(index) {
+            this.loadedIndices.add(index);
+            const item = this.getChild(index);
+            if (item) {
+                item.loaded();
+            }
+        }
+
+        isLoaded(index) {
+            return this.loadedIndices.has(index);
+        }
+
+        /**
+         * 読み込み状態を消す(manifestを切り替えるとき)
+         */
+        reset() {
+            this.loadedIndices.clear();
+        }
+
         /**
          * 要素が DOM に挿入されるたびに呼び出されます。
          * リソースの取得やレンダリングなどの、セットアップ コードの実行に役立ちます。
@@ -255,26 +380,6 @@ async function run() {
             this.mangaViewer = mangaViewer;
         }
 
-        /**
-         * 子要素をdeactivateする
-         */
-        deactivate() {
-            const children = this.children;
-            for (const child of children) {
-                child.classList.remove('active');
-            }
-        }
-
-        /**
-         * 特定の子要素のみをactivateする
-         * @param index
-         */
-        activate(index) {
-            this.deactivate();
-            const item = this.children[index];
-            item.classList.add('active');
-        }
-
         /**
          * 子要素を追加する。ImageListItem以外は無視。
          * @param newChild {ListViewItem} リストの子要素
@@ -282,14 +387,15 @@ async function run() {
         appendChild(newChild) {
             if (newChild instanceof ListViewItem) {
                 super.appendChild(newChild);
-
-                // loading状態に設定
-                newChild.loading();
             }
         }
 
+        /**
+         * 子要素を取得する。見える範囲外で作られていなければundefined。
+         * @param index 画像の番号
+         */
         getChild(index) {
-            return this.querySelectorAll('.image-list-item')[index];
+            return this.querySelector('.image-list-item[data-index="' + index + '"]') || undefined;
         }
     }
 
@@ -351,6 +457,12 @@ async function run() {
                         this.imageViewer.viewer.move_mouseup();
                     }
                 });
+                // 拡大縮小
+                this.addEventListener('wheel', (event) => {
+                    if (!this.imageViewer.zoom) return;
+                    event.preventDefault();
+                    this.imageViewer.zoom(event.deltaY < 0 ? 1.1 : 1 / 1.1);
+                });
             }
         }
 
@@ -402,8 +514,18 @@ async function run() {
                 if (!manifestID) {
                     manifestID = this.imageViewer.viewer.now().manifest_id();
                 }
-                let imageID = this.image.src;
+                // Image APIで回転、グレースケールにしたURLではなく元のURL
+                let imageID = (this.imageViewer.viewer.image_src && this.imageViewer.viewer.image_src()) || this.image.src;
                 let item = new CurationItem(manifestID, imageID, this.imageViewer.viewer.label() + '_' + this.imageViewer.viewer.image_label(), origin, event, this.image);
+                if (item.is_empty()) {
+                    item.free();
+                    this.area.classList.add('hide');
+                    return;
+                }
+                // Image APIから切り取った範囲を取得できるようにする
+                if (this.imageViewer.viewer.image_service) {
+                    item.set_service(this.imageViewer.viewer.image_service());
+                }
                 // crop image
                 {
                     let canvas = document.createElement('canvas');
@@ -477,10 +599,10 @@ async function run() {
 
         /**
          * 子要素を追加する。View以外は無視。
-         * @param newChild {ListView,IconView, CurationListView} 子要素
+         * @param newChild {ListView,IconView,MetadataView,CurationListView} 子要素
          */
         appendChild(newChild) {
-            if (newChild instanceof ListView || newChild instanceof IconView || newChild instanceof CurationListView) {
+            if (newChild instanceof ListView || newChild instanceof IconView || newChild instanceof MetadataView || newChild instanceof CurationListView) {
                 super.appendChild(newChild);
             }
         }
@@ -512,11 +634,13 @@ async function run() {
      * ビューア本体
      */
     class IIIFMangaViewer extends BasicViewer {
-        constructor(url) {
+        constructor(url, result) {
             super();
             if (url) {
                 this.setAttribute('manifest', url);
             }
+            // 検索結果から開く
+            this.result = result;
             // this.initialize();
         }
 
@@ -526,6 +650,9 @@ async function run() {
          * [参考](https://developers.google.com/web/fundamentals/web-components/customelements?hl=ja)
          */
         disconnectedCallback() {
+            this.closeComparison();
+            syncGroup.leave(this.syncId);
+            mangaViewers.delete(this.syncId);
             // メモリ開放
             this.viewer.free();
         }
@@ -554,6 +681,8 @@ async function run() {
          */
         connectedCallback() {
             viewerCounter++;
+            this.syncId = viewerCounter;
+            mangaViewers.set(this.syncId, this);
 
             // card
             this.classList.add('card');
@@ -612,6 +741,34 @@ async function run() {
                         li.appendChild(a);
                         dropdown.appendChild(li);
                     }
+                    {
+                        const li = document.createElement('li');
+                        const a = document.createElement('a');
+                        a.innerHTML =
+                            '<i class="material-icons">compare</i>Compare';
+                        a.onclick = () => {
+                            this.compare();
+                        };
+                        li.appendChild(a);
+                        dropdown.appendChild(li);
+                    }
+                    {
+                        // 同期の方法(全ビューアで共通)
+                        const li = document.createElement('li');
+                        const a = document.createElement('a');
+                        const label = () => {
+                            a.innerHTML = syncGroup.mode() === SyncMode.Relative ?
+                                '<i class="material-icons">compare_arrows</i>Sync: relative' :
+                                '<i class="material-icons">compare_arrows</i>Sync: absolute';
+                        };
+                        label();
+                        a.onclick = () => {
+                            syncGroup.set_mode(syncGroup.mode() === SyncMode.Relative ? SyncMode.Absolute : SyncMode.Relative);
+                            label();
+                        };
+                        li.appendChild(a);
+                        dropdown.appendChild(li);
+                    }
 
                     navBar.appendChild(dropdown);
                 }
@@ -640,6 +797,42 @@ async function run() {
                     li.appendChild(a);
                     ulL.appendChild(li);
                 }
+                {
+                    const li = document.createElement('li');
+                    const a = document.createElement('a');
+                    a.innerHTML =
+                        '<i class="material-icons">info_outline</i>';
+                    a.onclick = () => {
+                        this.metadataView.onOff();
+                    };
+                    this.metadataViewIcon = a;
+                    li.appendChild(a);
+                    ulL.appendChild(li);
+                }
+                {
+                    const li = document.createElement('li');
+                    const a = document.createElement('a');
+                    a.innerHTML =
+                        '<i class="material-icons">view_carousel</i>';
+                    a.onclick = () => {
+                        this.filmstripView.onOff();
+                    };
+                    this.filmstripViewIcon = a;
+                    li.appendChild(a);
+                    ulL.appendChild(li);
+                }
+                {
+                    const li = document.createElement('li');
+                    const a = document.createElement('a');
+                    a.innerHTML =
+                        '<i class="material-icons">link</i>';
+                    a.onclick = () => {
+                        this.toggleSync();
+                    };
+                    this.syncIcon = a;
+                    li.appendChild(a);
+                    ulL.appendChild(li);
+                }
                 navWrapper.appendChild(ulL);
 
                 const label = document.createElement('span');
@@ -649,6 +842,20 @@ async function run() {
 
                 const ulR = document.createElement('ul');
                 ulR.classList.add('right', 'toolbar-icons');
+                {
+                    // Manifest内の検索(Content Searchに対応していれば表示する)
+                    const li = document.createElement('li');
+                    li.classList.add('hide');
+                    const a = document.createElement('a');
+                    a.innerHTML =
+                        '<i class="material-icons">find_in_page</i>';
+                    a.onclick = () => {
+                        this.toggleContentSearch();
+                    };
+                    this.contentSearchIcon = a;
+                    li.appendChild(a);
+                    ulR.appendChild(li);
+                }
                 {
                     const li = document.createElement('li');
                     const a = document.createElement('a');
@@ -721,15 +928,24 @@ async function run() {
                     filterDropdown = dropdown;
                     dropdown.id = id;
                     dropdown.classList.add('dropdown-content', 'filter-dropdown');
-                    let brightness, contrast, gradient, greyscale, invert;
+                    let brightness, contrast, gradient, greyscale, invert, pageOnly;
+                    // 表示中の画像(pageOnly)またはすべての画像を調整して表示し直す
                     let onchange = () => {
-                        viewerCanvas.style.filter =
-                            'brightness(' + brightness.value + '%) ' +
-                            'contrast(' + contrast.value + '%) ' +
-                            'grayscale(' + greyscale.value + '%) ' +
-                            'saturate(' + gradient.value + '%) ' +
-                            'invert(' + invert.value + '%) ';
+                        this.viewer.set_filter(brightness.value, contrast.value, gradient.value,
+                            invert.value, greyscale.value, pageOnly.checked);
+                        this.show(this.viewer.index);
                     };
+                    {
+                        // 調整する範囲
+                        const li = document.createElement('li');
+                        li.innerHTML =
+                            '<label>' +
+                            '   <input type="checkbox" class="filled-in" />' +
+                            '   <span>This page only</span>' +
+                            '</label>';
+                        pageOnly = li.querySelector('input');
+                        dropdown.appendChild(li);
+                    }
                     {
                         // brightness
                         const li = document.createElement('li');
@@ -825,6 +1041,38 @@ async function run() {
                         };
                         dropdown.appendChild(li);
                     }
+                    {
+                        // 回転と反転
+                        const li = document.createElement('li');
+                        li.classList.add('orientation');
+                        li.innerHTML =
+                            '<a class="rotate-left"><i class="material-icons">rotate_left</i></a>' +
+                            '<a class="rotate-right"><i class="material-icons">rotate_right</i></a>' +
+                            '<a class="mirror"><i class="material-icons">flip</i></a>' +
+                            '<a class="reset"><i class="material-icons">settings_backup_restore</i></a>';
+                        li.querySelector('.rotate-left').onclick = () => {
+                            this.viewer.rotate(false, pageOnly.checked);
+                            this.show(this.viewer.index);
+                        };
+                        li.querySelector('.rotate-right').onclick = () => {
+                            this.viewer.rotate(true, pageOnly.checked);
+                            this.show(this.viewer.index);
+                        };
+                        li.querySelector('.mirror').onclick = () => {
+                            this.viewer.mirror(pageOnly.checked);
+                            this.show(this.viewer.index);
+                        };
+                        li.querySelector('.reset').onclick = () => {
+                            this.viewer.reset_adjustment();
+                            brightness.value = 100;
+                            contrast.value = 100;
+                            gradient.value = 100;
+                            greyscale.value = 0;
+                            invert.value = 0;
+                            this.show(this.viewer.index);
+                        };
+                        dropdown.appendChild(li);
+                    }
                     navBar.appendChild(dropdown);
                 }
                 navWrapper.appendChild(ulR);
@@ -864,42 +1112,102 @@ async function run() {
             this.iconView = iconView;
             views.appendChild(iconView);
 
+            // MetadataViewを設定
+            const metadataView = document.createElement('metadata-view');
+            this.metadataView = metadataView;
+            views.appendChild(metadataView);
+
+            // FilmstripViewを設定(画像の下)
+            const filmstripView = document.createElement('filmstrip-view');
+            this.filmstripView = filmstripView;
+            this.appendChild(filmstripView);
+
+            // 項目がクリックされたら表示する(強調とスクロールはviewerが行う)
+            listView.addEventListener('image-select', (e) => {
+                this.show(e.detail);
+            });
+            filmstripView.addEventListener('image-select', (e) => {
+                this.show(e.detail);
+            });
+            iconView.addEventListener('image-select', (e) => {
+                this.show(e.detail);
+                // メニュー非表示
+                iconView.onOff();
+            });
 
             // viewerを設定
             this.viewer = new Viewer(viewerCanvas, listView, iconView);
 
             const manifestURL = this.getAttribute('manifest');
-            if (manifestURL) {
+            if (this.result) {
+                Viewer.open_search_result(viewerCanvas, listView, iconView, this.result).then((viewer) => {
+                    this.viewer.free();
+                    this.viewer = viewer;
+                    this.loaded();
+                }).catch((e) => {
+                    // manifestが無ければ消える(エラーは検索結果のタイトルを含むので文字列として表示する)
+                    toastText(String(e));
+                    this.remove();
+                });
+            } else if (manifestURL) {
                 fetch(manifestURL).then((response) => {
                     return response.text();
                 }).then((text) => {
+                    this.listView.reset();
                     if (!this.viewer.set_manifest(text)) {
                         // manifestの読み取りに失敗すると消える
                         this.remove();
                     }
+                    this.loaded();
+                });
+            }
+        }
 
-                    // navigationを設定
-                    this.label.innerHTML = this.viewer.label();
+        /**
+         * manifestを読み込んだ後の設定
+         */
+        loaded() {
+            // navigationを設定
+            this.label.innerHTML = this.viewer.label();
+            this.viewer.set_metadata_view(this.metadataView);
+            this.viewer.set_filmstrip_view(this.filmstripView);
+            this.contentSearchIcon.parentElement.classList.toggle('hide', !this.viewer.has_content_search());
 
-                    this.show(0);
+            // 読み込みを見ている画像
+            this.watchedImages = new Set();
+            this.show(0);
+        }
 
-                    // 裏でloadを実行
-                    let load = () => {
-                        for (let i = 0; i < this.viewer.size(); i++) {
-                            if (!this.viewer.is_loading(i)) {
-                                this.viewer.load(i);
-                            }
-                            // loadが完了したらimageListの状態を変える
-                            const image = this.viewer.get_image_elem(i);
-                            const item = this.listView.getChild(i);
-                            image.addEventListener('load', () => {
-                                item.loaded();
-                            });
-                        }
-                    };
-                    new Thread(load()).execute();
-                });
+        /**
+         * 表示する頁の前後だけを読み込む(残りは表示するときに読み込む)
+         * @param index {number} 表示する頁
+         */
+        preload(index) {
+            const from = Math.max(0, index - PRELOAD_PAGES);
+            const to = Math.min(this.viewer.size() - 1, index + PRELOAD_PAGES);
+            for (let i = from; i <= to; i++) {
+                if (!this.viewer.is_loading(i)) {
+                    this.viewer.load(i);
+                }
+                this.watchLoad(i);
+            }
+        }
+
+        /**
+         * 読み込みが完了したらimageListの状態を変える
+         * @param index {number}
+         */
+        watchLoad(index) {
+            if (!this.watchedImages || this.watchedImages.has(index)) return;
+            const image = this.viewer.get_image_elem(index);
+            if (!image) return;
+            this.watchedImages.add(index);
+            if (image.complete) {
+                this.listView.markLoaded(index);
             }
+            image.addEventListener('load', () => {
+                this.listView.markLoaded(index);
+            });
         }
 
         cropping() {
@@ -911,29 +1219,53 @@ async function run() {
             this.croppingIcon.classList.toggle('available');
         }
 
-        progress() {
-            let div = document.createElement('div');
-            div.innerHTML =
-                "<div class=\"progress\" style='position: absolute;top: 50%;left: 50%; width: 50%;transform: translate(-50%, -50%);'>\n" +
-                "    <div class='indeterminate'></div>" +
-                "</div>";
-            div = div.firstElementChild;
-            this.appendChild(div);
-            return div;
+        // 読み込み中の表示(1つを使い回す)
+        showProgress() {
+            if (!this.progressBar) {
+                let div = document.createElement('div');
+                div.innerHTML =
+                    "<div class=\"progress\" style='position: absolute;top: 50%;left: 50%; width: 50%;transform: translate(-50%, -50%);'>\n" +
+                    "    <div class='indeterminate'></div>" +
+                    "</div>";
+                this.progressBar = div.firstElementChild;
+            }
+            if (!this.progressBar.parentNode) {
+                this.appendChild(this.progressBar);
+            }
+        }
+
+        hideProgress() {
+            if (this.progressBar && this.progressBar.parentNode) {
+                this.progressBar.parentNode.removeChild(this.progressBar);
+            }
         }
 
         show(index) {
-            if (!this.viewer.show(index)) {
-                let progress = this.progress();
+            const shown = this.viewer.show(index);
+            this.preload(index);
+            // 読み込みを待つ間に別のページへ移ったら、読み込み後に戻らない
+            this.pendingIndex = shown ? null : index;
+            if (!shown) {
+                this.showProgress();
                 let elem = this.viewer.get_image_elem(index);
                 if (elem) {
                     elem.addEventListener('load', () => {
-                        this.removeChild(progress);
-                        this.show(index);
-                    });
+                        if (this.pendingIndex === index) {
+                            this.show(index);
+                        }
+                    }, {once: true});
                 }
             } else {
-                this.listView.activate(index);
+                this.hideProgress();
+                this.transform();
+                // 大きさが決まってから検索結果の強調を画像に合わせる
+                const image = this.viewer.get_image_elem(index);
+                if (image && !image.complete) {
+                    image.addEventListener('load', () => {
+                        this.transform();
+                    }, {once: true});
+                }
+                this.synchronize(syncGroup.page(this.syncId, index));
             }
         };
 
@@ -947,8 +1279,299 @@ async function run() {
 
         // 表示中のイメージを動かす
         move(newX, newY) {
-            let image = this.viewerCanvas.getImage();
-            image.style.transform = 'translate(' + newX + 'px,' + newY + 'px)';
+            this.transform();
+            this.synchronize(syncGroup.transform(this.syncId, newX, newY, this.viewer.zoom_level()));
+        }
+
+        // 表示中のイメージを拡大縮小する
+        zoom(factor) {
+            const zoom = this.viewer.zoom(factor);
+            const position = this.viewer.position();
+            if (!position) return;
+            this.transform();
+            this.synchronize(syncGroup.transform(this.syncId, position.x, position.y, zoom));
+            position.free();
+        }
+
+        // 表示中のイメージと検索結果の強調に位置、倍率、回転と反転を反映する
+        transform() {
+            this.viewer.render();
+        }
+
+        /**
+         * Manifest内の検索の表示/非表示
+         */
+        toggleContentSearch() {
+            if (this.contentSearchPanel) {
+                this.contentSearchPanel.classList.toggle('hide');
+                this.contentSearchIcon.classList.toggle('available');
+                return;
+            }
+            const id = 'content-search-terms' + this.syncId;
+            const panel = document.createElement('div');
+            panel.classList.add('content-search', 'card-panel');
+            panel.innerHTML =
+                '<div class="input-field">' +
+                '   <i class="material-icons prefix">search</i>' +
+                '   <input type="text" autocomplete="off" list="' + id + '">' +
+                '   <datalist id="' + id + '"></datalist>' +
+                '</div>' +
+                '<ul class="collection content-search-hits"></ul>';
+            const input = panel.querySelector('input');
+            const datalist = panel.querySelector('datalist');
+            input.onkeydown = (e) => {
+                if (e.key === 'Enter') {
+                    this.contentSearch(input.value);
+                }
+            };
+            input.oninput = () => {
+                this.autocomplete(input.value, datalist);
+            };
+            this.contentSearchPanel = panel;
+            this.contentSearchHits = panel.querySelector('.content-search-hits');
+            this.contentSearchIcon.classList.add('available');
+            this.appendChild(panel);
+            input.focus();
+        }
+
+        /**
+         * Manifest内を検索して結果を一覧にする
+         * @param q {string} 検索語
+         */
+        contentSearch(q) {
+            const url = this.viewer.content_search_url(q);
+            if (!q || !url) return;
+            fetch(url).then((response) => {
+                if (!response.ok) {
+                    throw new Error('Cannot search: ' + response.status);
+                }
+                return response.text();
+            }).then((text) => {
+                if (!this.viewer.set_content_search_results(text)) {
+                    throw new Error('Cannot read search results');
+                }
+                this.renderHits(JSON.parse(this.viewer.content_search_hits()));
+            }).catch((e) => {
+                toastText(e.message || String(e));
+            });
+        }
+
+        /**
+         * 検索語の候補を表示する
+         * @param q {string} 入力中の検索語
+         * @param datalist {HTMLDataListElement}
+         */
+        autocomplete(q, datalist) {
+            const url = this.viewer.autocomplete_url(q);
+            if (!q || !url) return;
+            fetch(url).then((response) => {
+                return response.ok ? response.text() : '{}';
+            }).then((text) => {
+                datalist.innerHTML = '';
+                for (const term of JSON.parse(this.viewer.autocomplete_terms(text))) {
+                    const option = document.createElement('option');
+                    option.value = term.match;
+                    datalist.appendChild(option);
+                }
+            }).catch(() => {
+            });
+        }
+
+        /**
+         * 検索結果を一覧にする(クリックでそのページを表示して強調する)
+         * @param hits {Array<{index: ?number, before: string, match: string, after: string}>}
+         */
+        renderHits(hits) {
+            const list = this.contentSearchHits;
+            list.innerHTML = '';
+            if (hits.length === 0) {
+                const li = document.createElement('li');
+                li.classList.add('collection-item');
+                li.textContent = 'No results';
+                list.appendChild(li);
+                return;
+            }
+            hits.forEach((hit, i) => {
+                const li = document.createElement('li');
+                li.classList.add('collection-item');
+                const mark = document.createElement('mark');
+                mark.textContent = hit.match;
+                li.append(hit.before, mark, hit.after);
+                if (hit.index !== null && hit.index !== undefined) {
+                    const page = document.createElement('span');
+                    page.classList.add('secondary-content');
+                    page.textContent = String(hit.index + 1);
+                    li.appendChild(page);
+                    li.onclick = () => {
+                        this.showHit(i, hit.index);
+                    };
+                }
+                list.appendChild(li);
+            });
+        }
+
+        /**
+         * 検索結果のページを表示して範囲を強調する
+         * @param i {number} 検索結果の番号
+         * @param index {number} ページ
+         */
+        showHit(i, index) {
+            // 読み込み中なら、showが読み込み後に表示し直して強調する
+            this.viewer.show_hit(i);
+            this.show(index);
+        }
+
+        /**
+         * 表示中の画像を他のビューアの表示中の画像(無ければ次の頁)と重ねて比べる
+         */
+        compare() {
+            if (this.comparison) {
+                this.closeComparison();
+                return;
+            }
+            let other = this;
+            let index = this.viewer.index + 1;
+            for (const mangaViewer of mangaViewers.values()) {
+                if (mangaViewer !== this && mangaViewer.viewer && mangaViewer.viewer.size() > 0) {
+                    other = mangaViewer;
+                    index = mangaViewer.viewer.index;
+                    break;
+                }
+            }
+            if (index >= other.viewer.size()) {
+                M.toast({html: 'No image to compare'});
+                return;
+            }
+            if (!other.viewer.is_loading(index)) {
+                other.viewer.load(index);
+            }
+            const comparison = this.viewer.compare(other.viewer, index);
+            if (!comparison) return;
+            this.comparison = comparison;
+
+            // 読み込みが終わったら描き直す
+            for (const image of [this.viewer.get_image_elem(this.viewer.index), other.viewer.get_image_elem(index)]) {
+                if (image) {
+                    image.addEventListener('load', () => {
+                        if (this.comparison === comparison) comparison.render();
+                    });
+                }
+            }
+
+            // 境界の移動と位置合わせ(画像の移動はしない)
+            const element = comparison.element();
+            element.addEventListener('mousedown', (event) => {
+                event.stopPropagation();
+                comparison.mousedown(event);
+            });
+            element.addEventListener('mousemove', (event) => {
+                event.stopPropagation();
+                comparison.mousemove(event);
+            });
+            element.addEventListener('mouseup', (event) => {
+                event.stopPropagation();
+                comparison.mouseup();
+            });
+            element.addEventListener('mouseleave', () => {
+                comparison.mouseup();
+            });
+
+            // 操作パネル
+            const panel = document.createElement('div');
+            panel.classList.add('comparison-controls', 'card-panel');
+            panel.innerHTML =
+                '<p class="modes">' +
+                '<a class="btn-flat" data-mode="swipe">Swipe</a>' +
+                '<a class="btn-flat" data-mode="blend">Blend</a>' +
+                '<a class="btn-flat" data-mode="difference">Difference</a>' +
+                '<a class="btn-flat close"><i class="material-icons">close</i></a>' +
+                '</p>' +
+                '<label>Opacity</label><p class="range-field"><input type="range" class="opacity" min="0" max="100" value="50"></p>' +
+                '<label>Rotation</label><p class="range-field"><input type="range" class="rotation" min="-180" max="180" value="0"></p>' +
+                '<label>Scale</label><p class="range-field"><input type="range" class="scale" min="50" max="200" value="100"></p>';
+            const modes = {
+                swipe: CompareMode.Swipe,
+                blend: CompareMode.Blend,
+                difference: CompareMode.Difference,
+            };
+            panel.querySelectorAll('[data-mode]').forEach((a) => {
+                a.onclick = () => {
+                    comparison.set_mode(modes[a.getAttribute('data-mode')]);
+                };
+            });
+            panel.querySelector('.close').onclick = () => {
+                this.closeComparison();
+            };
+            panel.querySelector('.opacity').oninput = (e) => {
+                comparison.set_opacity(e.target.value / 100);
+            };
+            panel.querySelector('.rotation').oninput = (e) => {
+                comparison.set_rotation(Number(e.target.value));
+            };
+            panel.querySelector('.scale').oninput = (e) => {
+                comparison.set_scale(e.target.value / 100);
+            };
+            this.comparisonPanel = panel;
+            this.appendChild(panel);
+
+            comparison.render();
+        }
+
+        /**
+         * 比較をやめる
+         */
+        closeComparison() {
+            if (!this.comparison) return;
+            this.comparison.close();
+            this.comparison.free();
+            this.comparison = null;
+            this.comparisonPanel.remove();
+            this.comparisonPanel = null;
+        }
+
+        /**
+         * 同期の組に参加する/抜ける
+         */
+        toggleSync() {
+            if (syncGroup.contains(this.syncId)) {
+                syncGroup.leave(this.syncId);
+                this.syncIcon.classList.remove('available');
+            } else {
+                const position = this.viewer.position();
+                const x = position ? position.x : 0;
+                const y = position ? position.y : 0;
+                if (position) position.free();
+                syncGroup.join(this.syncId, this.viewer.size(), this.viewer.index, x, y, this.viewer.zoom_level());
+                this.syncIcon.classList.add('available');
+            }
+        }
+
+        /**
+         * 他のビューアに状態を反映する
+         * @param json {string} SyncGroupが返した状態の配列
+         */
+        synchronize(json) {
+            for (const change of JSON.parse(json)) {
+                const mangaViewer = mangaViewers.get(change.id);
+                if (mangaViewer) {
+                    mangaViewer.follow(change);
+                }
+            }
+        }
+
+        /**
+         * 同期した状態にする
+         * @param change {{page: number, x: number, y: number, zoom: number}}
+         */
+        follow(change) {
+            if (change.page !== this.viewer.index) {
+                this.show(change.page);
+            }
+            // 読み込み中なら頁だけ移動する
+            if (change.page === this.viewer.index) {
+                this.viewer.set_transform(change.x, change.y, change.zoom);
+                this.transform();
+            }
         }
     }
 
@@ -1190,6 +1813,26 @@ async function run() {
                             link.click();
 
                             link.remove();
+                            this.viewer.mark_saved();
+                        };
+                        li.appendChild(a);
+                        dropdown.appendChild(li);
+                    }
+                    {
+                        // 名前を付けてブラウザに保存
+                        const li = document.createElement('li');
+                        const a = document.createElement('a');
+                        a.classList.add('save_as');
+                        a.innerHTML =
+                            '<i class="material-icons">bookmark</i>Save As...';
+                        a.onclick = () => {
+                            const name = prompt('Curation name', this.viewer.name() || '');
+                            if (!name) return;
+                            if (this.viewer.save_as(name)) {
+                                this.updateLabel();
+                            } else {
+                                M.toast({html: '<i class="material-icons error left">error</i>Save Failed'});
+                            }
                         };
                         li.appendChild(a);
                         dropdown.appendChild(li);
@@ -1213,11 +1856,6 @@ async function run() {
                 navWrapper.appendChild(ulL);
 
                 const label = document.createElement('span');
-                if (CurationViewer.curationViewer === this) {
-                    label.innerText = 'Curation Viewer (Default)';
-                } else {
-                    label.innerText = 'Curation Viewer';
-                }
                 this.label = label;
                 navWrapper.appendChild(label);
 
@@ -1434,6 +2072,55 @@ async function run() {
 
             // viewerを設定
             this.viewer = new WasmCurationViewer(this.viewerCanvas);
+
+            // 規定のCurationViewerは最後に開いたキュレーションを復元する
+            if (CurationViewer.curationViewer === this && this.viewer.restore()) {
+                this.reload();
+            }
+            this.updateLabel();
+        }
+
+        /**
+         * 名前を表示する
+         */
+        updateLabel() {
+            let label = CurationViewer.curationViewer === this ? 'Curation Viewer (Default)' : 'Curation Viewer';
+            const name = this.viewer.name();
+            if (name) {
+                label += ' - ' + name;
+            }
+            this.label.innerText = label;
+        }
+
+        /**
+         * viewerのitemでリストを作り直し、最初のitemを表示する
+         */
+        reload() {
+            while (this.listView.firstChild) {
+                this.listView.firstChild.remove();
+            }
+            for (let i = 0; i < this.viewer.size(); i++) {
+                this.listView.appendChild(this.viewer.get(i));
+            }
+            if (this.viewer.size() > 0) {
+                this.viewer.show_by_index(0);
+                this.listView.activate(0);
+                this.classList.remove('hide');
+            }
+            this.updateLabel();
+        }
+
+        /**
+         * ブラウザに保存されたキュレーションを開く
+         * @param name {string}
+         * @return {boolean}
+         */
+        open(name) {
+            if (!this.viewer.open(name)) {
+                return false;
+            }
+            this.reload();
+            return true;
         }
 
         cropping() {
@@ -1459,7 +2146,9 @@ async function run() {
          * @param item {CurationItem}
          */
         push = (item) => {
-            this.viewer.push(item);
+            if (!this.viewer.push(item)) {
+                return;
+            }
             this.viewer.show_last();
 
             this.listView.appendChild(item);
@@ -1477,48 +2166,21 @@ async function run() {
             return this.viewer.swap(oldindex, newindex)
         }
 
-        fromJson = (jsonText) => {
+        /**
+         * JSONファイルのキュレーションを開き、ファイル名でブラウザにも保存する
+         * 切り取った画像はviewerが用意する
+         * @param jsonText {string}
+         * @param name {string}
+         */
+        fromJson = (jsonText, name) => {
             if (CurationViewer.curationViewer === this) {
                 return;
             }
             if (this.viewer.set_items(jsonText)) {
-                let promises = [];
-                for (let i = 0; i < this.viewer.size(); i++) {
-                    const promise = new Promise(() => {
-                        let item = this.viewer.get(i);
-                        const image = document.createElement('img');
-                        image.crossOrigin = "Anonymous";
-                        image.src = item.image_id();
-                        image.onload = () => {
-                            let canvas = document.createElement('canvas');
-                            let [sx, sy, sw, sh, dx, dy, dw, dh] =
-                                [
-                                    item.get_x_start(),
-                                    item.get_y_start(),
-                                    item.get_x_end() - item.get_x_start(),
-                                    item.get_y_end() - item.get_y_start(),
-                                    0,
-                                    0,
-                                    item.get_x_end() - item.get_x_start(),
-                                    item.get_y_end() - item.get_y_start()
-                                ];
-                            canvas.width = dw;
-                            canvas.height = dh;
-                            canvas.getContext('2d').drawImage(image, sx, sy, sw, sh, dx, dy, dw, dh);
-
-                            let img = new Image();
-                            img.src = canvas.toDataURL('image/png');
-
-                            item.set_image(img);
-                            this.push(item);
-                            // resolve(item.label());
-                        }
-                    });
-                    promises.push(promise);
+                if (name) {
+                    this.viewer.save_as(name);
                 }
-
-                Promise.all(promises);
-
+                this.reload();
             } else {
                 M.Toast({html: '<i class="material-icons error left">error</i>Parse Failed'});
                 this.remove();
@@ -1537,10 +2199,30 @@ async function run() {
      * 検索バー
      */
     class SearchBar extends HTMLElement {
-        constructor(cards, preloader) {
+        constructor(cards) {
             super();
-            this.cards = cards;
-            this.preloader = preloader;
+            this.history = new SearchHistory();
+            this.searchView = new SearchView(cards);
+            // 検索サービス
+            this.providers = [
+                WasmSearchProvider.europeana(EUROPEANA_KEY),
+                WasmSearchProvider.japan_search(),
+            ];
+            this.provider = this.providers[0];
+            // すべてのサービスを横断する検索
+            this.federated = new FederatedSearch();
+            this.federated.add(WasmSearchProvider.europeana(EUROPEANA_KEY));
+            this.federated.add(WasmSearchProvider.japan_search());
+            // 最後に始めた検索(古い検索のレスポンスを無視する)
+            this.searchCount = 0;
+            // 選ばれた結果を開く
+            cards.addEventListener('result-open', (e) => {
+                openResult(e.detail);
+            });
+            // 選ばれたページを取得する
+            cards.addEventListener('result-page', (e) => {
+                this.fetch(e.detail);
+            });
         }
 
         /**
@@ -1597,6 +2279,34 @@ async function run() {
             };
             this.appendChild(search_field);
 
+            // 検索サービスの選択
+            {
+                let provider = document.createElement('div');
+                provider.innerHTML =
+                    '<div class="input-field">\n' +
+                    '   <i class="material-icons prefix">public</i>\n' +
+                    '   <select></select>\n' +
+                    '   <label>Provider</label>' +
+                    '</div>';
+                provider = provider.firstElementChild;
+                const providerSelect = provider.querySelector('select');
+                this.providers.forEach((p, i) => {
+                    const option = document.createElement('option');
+                    option.value = String(i);
+                    option.innerText = p.name();
+                    providerSelect.appendChild(option);
+                });
+                const all = document.createElement('option');
+                all.value = 'all';
+                all.innerText = 'All providers';
+                providerSelect.appendChild(all);
+                providerSelect.onchange = () => {
+                    this.provider = providerSelect.value === 'all' ? null : this.providers[Number(providerSelect.value)];
+                };
+                this.appendChild(provider);
+                M.FormSelect.init(providerSelect, {});
+            }
+
             // ドロップダウンボタン
             let dropdownSwitch = document.createElement('div');
             dropdownSwitch.innerHTML =
@@ -1723,7 +2433,13 @@ async function run() {
 
             const query = search_field.querySelector('#icon_query').value;
             if (!query) return;
-            const searchQuery = new SearchQuery(query);
+            let searchQuery;
+            try {
+                searchQuery = SearchQuery.parse(query);
+            } catch (e) {
+                toastText(String(e));
+                return;
+            }
 
             // テーマ
             if (themeSelect.value) {
@@ -1746,120 +2462,83 @@ async function run() {
                 if (rows < 0) rows = 10;
                 searchQuery.set_rows(rows);
             }
-            // todo post query
-            let json = searchQuery.json();
-            this.clear();
+            this.fetch(searchQuery);
+        }
 
-            const url = '/search';
-            const headers = {
-                'Accept': 'application/json',
-                'Content-Type': 'application/json'
-            };
-            this.preloading();
-            fetch(url, {
-                method: 'POST',
-                headers,
-                body: json,
+        /**
+         * 検索してsearchViewに表示する
+         * サービスが選ばれていなければすべてのサービスに並行して問い合わせる
+         * @param searchQuery {SearchQuery}
+         */
+        fetch(searchQuery) {
+            const count = ++this.searchCount;
+            const headers = {'Accept': 'application/json'};
+            this.searchView.loading();
+            if (!this.provider) {
+                const search = this.federated;
+                const urls = JSON.parse(search.start(searchQuery));
+                Promise.all(urls.map((url, i) => {
+                    // 条件を扱えないサービスは失敗として記録済み
+                    if (!url) return Promise.resolve();
+                    return fetch(url, {headers}).then(res => {
+                        return res.text()
+                    }).then(text => {
+                        if (count === this.searchCount) search.receive(i, text);
+                    }).catch(err => {
+                        if (count === this.searchCount) search.fail(i, String(err));
+                    });
+                })).then(() => {
+                    if (count !== this.searchCount) return;
+                    const failures = JSON.parse(search.failures());
+                    if (failures.length === urls.length) {
+                        this.searchView.error(failures.map(f => f.provider + ': ' + f.message).join(' / '));
+                        return;
+                    }
+                    for (const failure of failures) {
+                        toastText(failure.provider + ': ' + failure.message);
+                    }
+                    this.showResults(searchQuery, search.results());
+                });
+                return;
+            }
+
+            const provider = this.provider;
+            new Promise(resolve => {
+                // 条件を扱えなければ例外になる
+                resolve(provider.url(searchQuery));
+            }).then(url => {
+                return fetch(url, {headers});
             }).then(res => {
                 return res.text()
             }).then(text => {
-                const results = new SearchResults(text);
-                if (!results) return;
-                for (let i = 0; i < results.len(); i++) {
-                    const result = results.get(i);
-                    this.appendCard(new SearchCard(result));
-                }
-                this.preloading();
+                if (count !== this.searchCount) return;
+                this.showResults(searchQuery, provider.parse(text));
             }).catch(err => {
+                if (count !== this.searchCount) return;
+                this.searchView.error(String(err));
             })
         }
 
         /**
-         * preloaderの表示/非表示
+         * 検索結果を表示する
+         * 履歴には最初のページだけ残す
+         * @param searchQuery {SearchQuery}
+         * @param results {SearchResults}
          */
-        preloading() {
-            this.preloader.classList.toggle('hide');
-        }
-
-        /**
-         * 前回の検索結果を消去する
-         */
-        clear() {
-            this.cards.innerHTML = '';
+        showResults(searchQuery, results) {
+            if (searchQuery.start() <= 1 && !searchQuery.cursor()) {
+                this.history.record(searchQuery, results.total());
+            }
+            this.searchView.results(results, searchQuery);
         }
 
         appendChild(newChild) {
             this.content.appendChild(newChild);
         }
-
-        appendCard(newCard) {
-            this.cards.appendChild(newCard);
-        }
     }
 
     customElements.define('search-bar', SearchBar);
 
-    /**
-     * Manifestの検索結果(1件)
-     */
-    class SearchCard extends HTMLElement {
-        /**
-         *
-         * @param result {SearchResult}
-         */
-        constructor(result) {
-            super();
-            this.result = result;
-        }
-
-        /**
-         * 要素が DOM に挿入されるたびに呼び出されます。
-         * リソースの取得やレンダリングなどの、セットアップ コードの実行に役立ちます。
-         * 一般に、この時点まで作業を遅らせるようにする必要があります。
-         * [参考](https://developers.google.com/web/fundamentals/web-components/customelements?hl=ja)
-         */
-        connectedCallback() {
-            // card
-            this.classList.add('card');
-            const cardImage = document.createElement('div');
-            cardImage.classList.add('card-image');
-            {
-                const img = document.createElement('img');
-                if (this.result.thumbnail()) {
-                    img.src = this.result.thumbnail();
-                } else {
-                    img.src = 'https://material.io/tools/icons/static/icons/baseline-collections-24px.svg';
-                }
-                cardImage.appendChild(img);
-            }
-            {
-                const span = document.createElement('span');
-                span.classList.add('card-title');
-                span.innerHTML = this.result.title();
-                cardImage.appendChild(span);
-            }
-            {
-                const fab = document.createElement('a');
-                fab.classList.add('btn-floating', 'halfway-fab', 'waves-effect', 'waves-light', 'btn-large');
-                fab.innerHTML = '<i class="material-icons">launch</i>';
-                cardImage.appendChild(fab);
-
-                // onclick
-                fab.onclick = () => {
-                    open(this.result.url());
-                };
-            }
-            this.appendChild(cardImage);
-
-            const cardContent = document.createElement('div');
-            cardContent.classList.add('card-content');
-            cardContent.innerHTML = this.result.description();
-            this.appendChild(cardContent);
-        }
-    }
-
-    customElements.define('search-card', SearchCard);
-
     /**
      * Manifestの検索を行うmodal
      */
@@ -1894,61 +2573,9 @@ async function run() {
             const cards = document.createElement('div');
             cards.classList.add('cards');
 
-            // preloader
-            let preloader = document.createElement('div');
-            preloader.innerHTML =
-                '<div class="row">' +
-                '<div class="col offset-m3 m6 center">' +
-                '<div class="preloader-wrapper big active">\n' +
-                '<div class="spinner-layer spinner-blue">\n' +
-                '<div class="circle-clipper left">\n' +
-                '<div class="circle"></div>\n' +
-                '</div>' +
-                '<div class="gap-patch">\n' +
-                '<div class="circle"></div>\n' +
-                '</div>' +
-                '<div class="circle-clipper right">\n' +
-                '<div class="circle"></div>\n' +
-                '</div>\n' +
-                '</div>' +
-                '<div class="spinner-layer spinner-red">\n' +
-                '<div class="circle-clipper left">\n' +
-                '<div class="circle"></div>\n' +
-                '</div><div class="gap-patch">\n' +
-                '<div class="circle"></div>\n' +
-                '</div><div class="circle-clipper right">\n' +
-                '<div class="circle"></div>\n' +
-                '</div>\n' +
-                '</div>\n' +
-                '<div class="spinner-layer spinner-yellow">\n' +
-                '<div class="circle-clipper left">\n' +
-                '<div class="circle"></div>\n' +
-                '</div><div class="gap-patch">\n' +
-                '<div class="circle"></div>\n' +
-                '</div><div class="circle-clipper right">\n' +
-                '<div class="circle"></div>\n' +
-                '</div>\n' +
-                '</div>\n' +
-                '<div class="spinner-layer spinner-green">\n' +
-                '<div class="circle-clipper left">\n' +
-                '<div class="circle"></div>\n' +
-                '</div><div class="gap-patch">\n' +
-                '<div class="circle"></div>\n' +
-                '</div><div class="circle-clipper right">\n' +
-                '<div class="circle"></div>\n' +
-                '</div>\n' +
-                '</div>' +
-                '</div>' +
-                '</div>' +
-                '</div>';
-            preloader = preloader.firstElementChild;
-            preloader.classList.add('hide');
-
             // 検索バーの設置
-            const search_bar = new SearchBar(cards, preloader);
+            const search_bar = new SearchBar(cards);
             this.appendChild(search_bar);
-            // preloaderの設置
-            this.appendChild(preloader);
             // cardsの設置
             this.appendChild(cards);
         }
@@ -2033,7 +2660,11 @@ async function run() {
          */
         connectedCallback() {
             this.classList.add('modal');
-            M.Modal.init(this, {});
+            M.Modal.init(this, {
+                onOpenStart: () => {
+                    this.renderSaved();
+                },
+            });
 
             // content
             const content = document.createElement('div');
@@ -2075,13 +2706,119 @@ async function run() {
             a.onclick = () => {
                 let reader = new FileReader();
                 reader.readAsText(this.input.files[0]);
+                const file = this.input.files[0];
+                reader.readAsText(file);
                 reader.onload = () => {
                     const cv = new CurationViewer();
                     document.getElementById('viewers').appendChild(cv);
-                    cv.fromJson(reader.result);
+                    cv.fromJson(reader.result, file.name.replace(/\.json$/, ''));
                 }
             };
             this.footerAppendChild(a);
+
+            // ブラウザに保存されたキュレーション
+            const savedTitle = document.createElement('h5');
+            savedTitle.innerText = 'Saved Curations';
+            this.appendChild(savedTitle);
+            const saved = document.createElement('ul');
+            saved.classList.add('collection', 'saved-curations');
+            this.saved = saved;
+            this.appendChild(saved);
+            saved.onclick = (e) => {
+                const item = e.target.closest('[data-name]');
+                if (!item) return;
+                const name = item.getAttribute('data-name');
+                if (e.target.closest('.delete')) {
+                    this.library().delete_curation(name);
+                    this.renderSaved();
+                    return;
+                }
+                const cv = new CurationViewer();
+                document.getElementById('viewers').appendChild(cv);
+                if (!cv.open(name)) {
+                    cv.remove();
+                    M.toast({html: '<i class="material-icons error left">error</i>Open Failed'});
+                }
+                M.Modal.getInstance(this).close();
+            };
+
+            // ライブラリの読み込み
+            const importInput = document.createElement('input');
+            importInput.type = 'file';
+            importInput.accept = 'application/json';
+            importInput.classList.add('hide');
+            importInput.onchange = () => {
+                const reader = new FileReader();
+                reader.readAsText(importInput.files[0]);
+                reader.onload = () => {
+                    if (!this.library().import_library(reader.result)) {
+                        M.toast({html: '<i class="material-icons error left">error</i>Import Failed'});
+                    }
+                    importInput.value = '';
+                    this.renderSaved();
+                };
+            };
+            this.footerAppendChild(importInput);
+            const importButton = document.createElement('a');
+            importButton.classList.add('waves-effect', 'waves-blue', 'btn-flat', 'secondary-color');
+            importButton.innerHTML =
+                '<i class="material-icons left">file_upload</i>Import Library';
+            importButton.onclick = () => {
+                importInput.click();
+            };
+            this.footerAppendChild(importButton);
+
+            // ライブラリの書き出し
+            const exportButton = document.createElement('a');
+            exportButton.classList.add('waves-effect', 'waves-blue', 'btn-flat', 'secondary-color');
+            exportButton.innerHTML =
+                '<i class="material-icons left">file_download</i>Export Library';
+            exportButton.onclick = () => {
+                const json = this.library().export_library();
+                if (!json) {
+                    M.toast({html: '<i class="material-icons error left">error</i>Export Failed'});
+                    return;
+                }
+                const link = document.createElement('a');
+                link.classList.add('hide');
+                document.body.appendChild(link);
+                link.href = URL.createObjectURL(new Blob([json], {type: "application/json;charset=utf-8"}));
+                link.download = 'CurationLibrary.json';
+                link.click();
+                link.remove();
+            };
+            this.footerAppendChild(exportButton);
+        }
+
+        /**
+         * 保存先は共通なので、規定のCurationViewerから操作する
+         * @return {WasmCurationViewer}
+         */
+        library() {
+            return CurationViewer.curationViewer.viewer;
+        }
+
+        /**
+         * 保存されたキュレーションの一覧を作り直す
+         */
+        renderSaved() {
+            while (this.saved.firstChild) {
+                this.saved.firstChild.remove();
+            }
+            if (!CurationViewer.curationViewer) return;
+            for (const name of JSON.parse(this.library().curation_names())) {
+                const li = document.createElement('li');
+                li.classList.add('collection-item');
+                li.setAttribute('data-name', name);
+                const a = document.createElement('a');
+                a.innerText = name;
+                li.appendChild(a);
+                const remove = document.createElement('a');
+                remove.classList.add('delete', 'secondary-content');
+                remove.innerHTML = '<i class="material-icons">delete</i>';
+                li.appendChild(remove);
+                this.saved.appendChild(li);
+            }
         }
 
         appendChild(newChild) {
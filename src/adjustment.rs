@@ -0,0 +1,186 @@
+use crate::image_api::ImageRequest;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SRC: &str = "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/full/full/0/native.jpg";
+
+    #[test]
+    fn css_test() {
+        let mut adjustment = Adjustment::default();
+        assert_eq!(adjustment.filter(ServerSide::default()), "brightness(100%) contrast(100%) saturate(100%) invert(0%) grayscale(0%)");
+        assert_eq!(adjustment.transform(ServerSide::default()), "");
+
+        adjustment.brightness = 150.0;
+        adjustment.grayscale = 100.0;
+        adjustment.rotate(false);
+        adjustment.mirror = true;
+        assert_eq!(adjustment.rotation, 270);
+        assert_eq!(adjustment.filter(ServerSide::default()), "brightness(150%) contrast(100%) saturate(100%) invert(0%) grayscale(100%)");
+        assert_eq!(adjustment.transform(ServerSide::default()), "rotate(270deg) scaleX(-1)");
+        // Image APIで処理した分はcssで重ねない
+        let server = ServerSide { rotation: 270, gray: true };
+        assert_eq!(adjustment.filter(server), "brightness(150%) contrast(100%) saturate(100%) invert(0%)");
+        assert_eq!(adjustment.transform(server), "scaleX(-1)");
+        // Image APIで回転した画像の読み込みが終わるまでは、足りない分をcssで回転する
+        assert_eq!(adjustment.transform(ServerSide { rotation: 90, gray: true }), "rotate(180deg) scaleX(-1)");
+    }
+
+    #[test]
+    fn rotate_region_test() {
+        let region = (0.1, 0.2, 0.3, 0.4);
+        // cssで回転するときはそのまま
+        assert_eq!(ServerSide::default().rotate_region(region), region);
+        assert_eq!(ServerSide { rotation: 90, gray: false }.rotate_region(region), (1.0 - 0.2 - 0.4, 0.1, 0.4, 0.3));
+        assert_eq!(ServerSide { rotation: 180, gray: false }.rotate_region(region), (1.0 - 0.1 - 0.3, 1.0 - 0.2 - 0.4, 0.3, 0.4));
+        assert_eq!(ServerSide { rotation: 270, gray: false }.rotate_region(region), (0.2, 1.0 - 0.1 - 0.3, 0.4, 0.3));
+    }
+
+    #[test]
+    fn server_side_test() {
+        let mut adjustment = Adjustment::default();
+        adjustment.rotate(true);
+        adjustment.grayscale = 100.0;
+        assert_eq!(adjustment.server_side(SRC, Some(2)), ServerSide { rotation: 90, gray: true });
+        assert_eq!(adjustment.src(SRC, Some(2)), "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/full/full/90/gray.jpg");
+        // level2未満やImage APIでなければすべてcssで行う
+        assert_eq!(adjustment.server_side(SRC, Some(1)), ServerSide::default());
+        assert_eq!(adjustment.src(SRC, None), SRC);
+        assert_eq!(adjustment.src("http://example.com/image.jpg", Some(2)), "http://example.com/image.jpg");
+        // 左右反転は回転より先に行うので、回転もcssで行う
+        adjustment.mirror = true;
+        assert_eq!(adjustment.server_side(SRC, Some(2)), ServerSide { rotation: 0, gray: true });
+        assert_eq!(Adjustment::default().src(SRC, Some(2)), SRC);
+    }
+
+    #[test]
+    fn applied_test() {
+        let rotated = "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/full/full/90/gray.jpg";
+        assert_eq!(ServerSide::applied(SRC, rotated), ServerSide { rotation: 90, gray: true });
+        assert_eq!(ServerSide::applied(SRC, SRC), ServerSide::default());
+        // 読み込み前(currentSrcが空)やImage APIでないもの
+        assert_eq!(ServerSide::applied(SRC, ""), ServerSide::default());
+        assert_eq!(ServerSide::applied("http://example.com/image.jpg", "http://example.com/image.jpg"), ServerSide::default());
+    }
+}
+
+/// 画像の見え方の調整
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Adjustment {
+    /// 明るさ(%)
+    pub brightness: f64,
+    /// コントラスト(%)
+    pub contrast: f64,
+    /// 彩度(%)
+    pub saturation: f64,
+    /// 色の反転(%)
+    pub invert: f64,
+    /// グレースケール(%)
+    pub grayscale: f64,
+    /// 時計回りの回転(90度ずつ)
+    pub rotation: u32,
+    /// 左右反転
+    pub mirror: bool,
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Self { brightness: 100.0, contrast: 100.0, saturation: 100.0, invert: 0.0, grayscale: 0.0, rotation: 0, mirror: false }
+    }
+}
+
+/// Image APIで処理する調整
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ServerSide {
+    /// 時計回りの回転(度)
+    pub rotation: u32,
+    pub gray: bool,
+}
+
+impl ServerSide {
+    /// 元のURLと比べて、表示中のURLの画像にImage APIが行った調整
+    pub fn applied(src: &str, shown: &str) -> ServerSide {
+        match (src.parse::<ImageRequest>(), shown.parse::<ImageRequest>()) {
+            (Ok(src), Ok(shown)) => ServerSide {
+                rotation: ((shown.rotation - src.rotation) % 360.0 + 360.0) as u32 % 360,
+                gray: shown.quality == "gray" && src.quality != "gray",
+            },
+            _ => ServerSide::default(),
+        }
+    }
+
+    /// Canvas上の範囲(割合)を、Image APIで回転した画像上の範囲にする
+    pub fn rotate_region(&self, (x, y, w, h): (f64, f64, f64, f64)) -> (f64, f64, f64, f64) {
+        match self.rotation {
+            90 => (1.0 - y - h, x, h, w),
+            180 => (1.0 - x - w, 1.0 - y - h, w, h),
+            270 => (y, 1.0 - x - w, h, w),
+            _ => (x, y, w, h),
+        }
+    }
+}
+
+impl Adjustment {
+    /// 90度回転する
+    pub fn rotate(&mut self, clockwise: bool) {
+        self.rotation = (self.rotation + if clockwise { 90 } else { 270 }) % 360;
+    }
+
+    /// Image APIで処理できる調整
+    /// 90度ずつの回転とqualityのgrayはlevel2から(左右反転は任意なのでcssで行う)
+    pub fn server_side(&self, src: &str, level: Option<u8>) -> ServerSide {
+        if level.map_or(true, |level| level < 2) || src.parse::<ImageRequest>().is_err() {
+            return ServerSide::default();
+        }
+        ServerSide {
+            // Image APIは反転してから回転するので、反転するときは回転もcssで行う
+            rotation: if self.mirror { 0 } else { self.rotation },
+            gray: self.grayscale >= 100.0,
+        }
+    }
+
+    /// Image APIで回転、グレースケールにした画像のURL
+    pub fn src(&self, src: &str, level: Option<u8>) -> String {
+        let server = self.server_side(src, level);
+        if server.rotation == 0 && !server.gray {
+            return src.to_string();
+        }
+        match src.parse::<ImageRequest>() {
+            Ok(mut request) => {
+                if server.rotation != 0 {
+                    request = request.rotation(server.rotation as f64, false);
+                }
+                if server.gray {
+                    request = request.quality("gray");
+                }
+                request.to_string()
+            }
+            Err(_) => src.to_string(),
+        }
+    }
+
+    /// cssのfilter
+    pub fn filter(&self, server: ServerSide) -> String {
+        let mut filter = format!("brightness({}%) contrast({}%) saturate({}%) invert({}%)",
+                                 self.brightness, self.contrast, self.saturation, self.invert);
+        if !server.gray {
+            filter += &format!(" grayscale({}%)", self.grayscale);
+        }
+        filter
+    }
+
+    /// cssのtransform(回転と反転)
+    /// Image APIで回転した分は除く
+    pub fn transform(&self, server: ServerSide) -> String {
+        let mut transform = Vec::new();
+        let rotation = (self.rotation + 360 - server.rotation % 360) % 360;
+        if rotation != 0 {
+            transform.push(format!("rotate({}deg)", rotation));
+        }
+        if self.mirror {
+            transform.push("scaleX(-1)".to_string());
+        }
+        transform.join(" ")
+    }
+}
@@ -0,0 +1,257 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Element, HtmlCanvasElement, HtmlImageElement, MouseEvent};
+
+use crate::viewer::log;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fit_test() {
+        // 縦長の画像は高さに合わせて中央に置く
+        assert_eq!(fit(500.0, 1000.0, 800.0, 600.0), (250.0, 0.0, 300.0, 600.0));
+        // 横長の画像は幅に合わせる
+        assert_eq!(fit(1600.0, 600.0, 800.0, 600.0), (0.0, 150.0, 800.0, 300.0));
+        assert_eq!(fit(0.0, 0.0, 800.0, 600.0), (0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn divider_test() {
+        assert_eq!(divider(200.0, 800.0), 0.25);
+        assert_eq!(divider(-10.0, 800.0), 0.0);
+        assert_eq!(divider(900.0, 800.0), 1.0);
+        assert_eq!(divider(10.0, 0.0), 0.5);
+    }
+}
+
+/// 画像を縦横比を保って枠に収めたときの(x, y, 幅, 高さ)
+pub fn fit(image_width: f64, image_height: f64, width: f64, height: f64) -> (f64, f64, f64, f64) {
+    if image_width <= 0.0 || image_height <= 0.0 {
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    let scale = (width / image_width).min(height / image_height);
+    let (w, h) = (image_width * scale, image_height * scale);
+    ((width - w) / 2.0, (height - h) / 2.0, w, h)
+}
+
+/// swipeの境界の位置(左端0.0から右端1.0)
+pub fn divider(x: f64, width: f64) -> f64 {
+    if width <= 0.0 {
+        return 0.5;
+    }
+    (x / width).max(0.0).min(1.0)
+}
+
+/// 2枚の画像の比べ方
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompareMode {
+    /// 境界の左に1枚目、右に2枚目を表示する(境界はドラッグで動かす)
+    Swipe,
+    /// 2枚目を半透明にして重ねる
+    Blend,
+    /// 画素ごとの差を表示する
+    Difference,
+}
+
+/// 2枚の画像を重ねて比べる
+/// 2枚目の画像は位置、回転、倍率で1枚目に合わせる
+#[wasm_bindgen]
+pub struct Comparison {
+    /// 画像を表示する部分(viewer-canvas)
+    container: Element,
+    canvas: HtmlCanvasElement,
+    base: HtmlImageElement,
+    other: HtmlImageElement,
+    mode: CompareMode,
+    /// swipeの境界
+    divider: f64,
+    /// blendの2枚目の不透明度
+    opacity: f64,
+    offset_x: f64,
+    offset_y: f64,
+    /// 回転(度)
+    rotation: f64,
+    scale: f64,
+    /// ドラッグを始めた位置とそのときの値
+    mousedown: Option<(f64, f64, f64, f64)>,
+}
+
+impl Comparison {
+    /// containerの上に重ねるcanvasを作る
+    pub fn new(container: &Element, base: HtmlImageElement, other: HtmlImageElement) -> Option<Self> {
+        let document = web_sys::window()?.document()?;
+        let canvas = document.create_element("canvas").ok()?.dyn_into::<HtmlCanvasElement>().ok()?;
+        canvas.class_list().add_1("comparison").ok()?;
+        // viewer-canvasのappendChildは画像しか受け付けないので、insertAdjacentElementで追加する
+        container.insert_adjacent_element("beforeend", &canvas).ok()?;
+        Some(Self {
+            container: container.clone(),
+            canvas,
+            base,
+            other,
+            mode: CompareMode::Swipe,
+            divider: 0.5,
+            opacity: 0.5,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            rotation: 0.0,
+            scale: 1.0,
+            mousedown: None,
+        })
+    }
+
+    fn context(&self) -> Option<CanvasRenderingContext2d> {
+        self.canvas.get_context("2d").ok()??.dyn_into::<CanvasRenderingContext2d>().ok()
+    }
+
+    /// 2枚目の画像を中心で回転、拡大縮小して描く
+    fn draw_other(&self, context: &CanvasRenderingContext2d, width: f64, height: f64) -> Result<(), JsValue> {
+        let (x, y, w, h) = fit(self.other.natural_width() as f64, self.other.natural_height() as f64, width, height);
+        context.save();
+        context.translate(width / 2.0 + self.offset_x, height / 2.0 + self.offset_y)?;
+        context.rotate(self.rotation.to_radians())?;
+        context.scale(self.scale, self.scale)?;
+        let result = context.draw_image_with_html_image_element_and_dw_and_dh(&self.other, x - width / 2.0, y - height / 2.0, w, h);
+        context.restore();
+        result
+    }
+}
+
+#[wasm_bindgen]
+impl Comparison {
+    /// 重ねたcanvas(イベントを受け取る要素)
+    pub fn element(&self) -> Element {
+        self.canvas.clone().into()
+    }
+
+    /// 2枚とも読み込み済みか否か
+    pub fn is_ready(&self) -> bool {
+        self.base.complete() && self.other.complete()
+    }
+
+    pub fn mode(&self) -> CompareMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CompareMode) {
+        self.mode = mode;
+        self.render();
+    }
+
+    pub fn set_divider(&mut self, divider: f64) {
+        self.divider = divider.max(0.0).min(1.0);
+        self.render();
+    }
+
+    pub fn set_opacity(&mut self, opacity: f64) {
+        self.opacity = opacity.max(0.0).min(1.0);
+        self.render();
+    }
+
+    /// 2枚目の画像の位置合わせ(px)
+    pub fn set_offset(&mut self, x: f64, y: f64) {
+        self.offset_x = x;
+        self.offset_y = y;
+        self.render();
+    }
+
+    /// 2枚目の画像の回転(度)
+    pub fn set_rotation(&mut self, rotation: f64) {
+        self.rotation = rotation;
+        self.render();
+    }
+
+    /// 2枚目の画像の倍率
+    pub fn set_scale(&mut self, scale: f64) {
+        if scale > 0.0 {
+            self.scale = scale;
+            self.render();
+        }
+    }
+
+    /// 表示領域の大きさに合わせて描き直す
+    pub fn render(&self) {
+        if !self.is_ready() {
+            return;
+        }
+        let context = match self.context() {
+            Some(context) => context,
+            None => return log("Cannot get context of comparison"),
+        };
+        let (width, height) = (self.container.client_width().max(0) as u32, self.container.client_height().max(0) as u32);
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+        let (width, height) = (width as f64, height as f64);
+
+        let (x, y, w, h) = fit(self.base.natural_width() as f64, self.base.natural_height() as f64, width, height);
+        let result = context.draw_image_with_html_image_element_and_dw_and_dh(&self.base, x, y, w, h).and_then(|_| {
+            match self.mode {
+                CompareMode::Swipe => {
+                    let divider = width * self.divider;
+                    context.save();
+                    context.begin_path();
+                    context.rect(divider, 0.0, width - divider, height);
+                    context.clip();
+                    let result = self.draw_other(&context, width, height);
+                    context.restore();
+                    // 境界線
+                    context.set_fill_style(&JsValue::from_str("white"));
+                    context.fill_rect(divider - 1.0, 0.0, 2.0, height);
+                    result
+                }
+                CompareMode::Blend => {
+                    context.set_global_alpha(self.opacity);
+                    let result = self.draw_other(&context, width, height);
+                    context.set_global_alpha(1.0);
+                    result
+                }
+                CompareMode::Difference => {
+                    context.set_global_composite_operation("difference")?;
+                    let result = self.draw_other(&context, width, height);
+                    context.set_global_composite_operation("source-over")?;
+                    result
+                }
+            }
+        });
+        if result.is_err() {
+            log("Cannot draw comparison");
+        }
+    }
+
+    /// mousedownイベント
+    /// swipeなら境界を、それ以外なら2枚目の画像の位置を動かし始める
+    pub fn mousedown(&mut self, event: MouseEvent) {
+        let (x, y) = (event.offset_x() as f64, event.offset_y() as f64);
+        self.mousedown = Some((x, y, self.offset_x, self.offset_y));
+        if self.mode == CompareMode::Swipe {
+            self.set_divider(divider(x, self.canvas.client_width() as f64));
+        }
+    }
+
+    /// mousemoveイベント
+    pub fn mousemove(&mut self, event: MouseEvent) {
+        let (origin_x, origin_y, offset_x, offset_y) = match self.mousedown {
+            Some(mousedown) => mousedown,
+            None => return,
+        };
+        let (x, y) = (event.offset_x() as f64, event.offset_y() as f64);
+        if self.mode == CompareMode::Swipe {
+            self.set_divider(divider(x, self.canvas.client_width() as f64));
+        } else {
+            self.set_offset(offset_x + x - origin_x, offset_y + y - origin_y);
+        }
+    }
+
+    /// mouseupイベント
+    pub fn mouseup(&mut self) {
+        self.mousedown = None;
+    }
+
+    /// 重ねたcanvasを取り除く
+    pub fn close(&self) {
+        self.canvas.remove();
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use crate::search::encode;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_content_search_test() {
+        let hits = parse_hits(include_str!("../test/content_search.json")).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].canvas, "https://example.org/manifest/canvas/p2");
+        assert_eq!(hits[0].xywh, Some((400, 100, 120, 40)));
+        assert_eq!(hits[0].before, "the great wave off Kanagawa with mount ");
+        assert_eq!(hits[0].matched, "fuji");
+        assert_eq!(hits[1].canvas, "https://example.org/manifest/canvas/p1");
+        assert_eq!(hits[1].xywh, Some((10, 20, 30, 40)));
+    }
+
+    #[test]
+    fn parse_without_hits_test() {
+        let json = r#"{"resources": [{"@id": "a1", "resource": {"chars": "wave"}, "on": "https://example.org/canvas/p3"}]}"#;
+        let hits = parse_hits(json).unwrap();
+        assert_eq!(hits[0].matched, "wave");
+        assert_eq!(hits[0].xywh, None);
+    }
+
+    #[test]
+    fn parse_autocomplete_test() {
+        let json = r#"{"@type": "search:TermList", "terms": [{"match": "fuji", "url": "https://example.org/search?q=fuji", "count": 3}, {"match": "fujisan"}]}"#;
+        let terms = parse_terms(json).unwrap();
+        assert_eq!(terms[0].matched, "fuji");
+        assert_eq!(terms[0].count, Some(3));
+        assert_eq!(terms[1].count, None);
+    }
+
+    #[test]
+    fn search_url_test() {
+        assert_eq!(search_url("https://example.org/search", "mount fuji"), "https://example.org/search?q=mount%20fuji");
+        assert_eq!(search_url("https://example.org/search?motivation=painting", "fuji"), "https://example.org/search?motivation=painting&q=fuji");
+    }
+}
+
+/// IIIF Content Search APIの検索結果(1件)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ContentSearchHit {
+    /// 当てはまったCanvasの@id
+    pub canvas: String,
+    /// Canvas上の範囲
+    pub xywh: Option<(u32, u32, u32, u32)>,
+    pub before: String,
+    #[serde(rename = "match")]
+    pub matched: String,
+    pub after: String,
+}
+
+/// 自動補完の候補
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Term {
+    #[serde(rename = "match")]
+    pub matched: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub count: Option<u32>,
+}
+
+/// 検索のURL
+pub fn search_url(service: &str, q: &str) -> String {
+    let separator = if service.contains('?') { '&' } else { '?' };
+    format!("{}{}q={}", service, separator, encode(q))
+}
+
+/// 検索結果のAnnotationListを読み込む
+/// hitsが無ければ、Annotationをそのまま検索結果とする
+pub fn parse_hits(json: &str) -> Result<Vec<ContentSearchHit>, serde_json::Error> {
+    let list: AnnotationList = serde_json::from_str(json)?;
+    let annotations: HashMap<&String, &Annotation> = list.resources.iter()
+        .map(|a| (&a.id, a))
+        .collect();
+
+    if list.hits.is_empty() {
+        return Ok(list.resources.iter().map(|a| a.hit(None)).collect());
+    }
+    let hits = list.hits.iter()
+        .filter_map(|hit| {
+            let annotation = hit.annotations.iter().filter_map(|id| annotations.get(id)).next()?;
+            Some(annotation.hit(Some(hit)))
+        })
+        .collect();
+    Ok(hits)
+}
+
+/// 自動補完の結果を読み込む
+pub fn parse_terms(json: &str) -> Result<Vec<Term>, serde_json::Error> {
+    let list: TermList = serde_json::from_str(json)?;
+    Ok(list.terms)
+}
+
+#[derive(Deserialize)]
+struct AnnotationList {
+    #[serde(default)]
+    resources: Vec<Annotation>,
+    #[serde(default)]
+    hits: Vec<Hit>,
+}
+
+#[derive(Deserialize)]
+struct Annotation {
+    #[serde(rename = "@id")]
+    id: String,
+    #[serde(default)]
+    resource: Option<Chars>,
+    on: Target,
+}
+
+impl Annotation {
+    fn hit(&self, hit: Option<&Hit>) -> ContentSearchHit {
+        let (canvas, xywh) = self.on.canvas_and_xywh();
+        let chars = self.resource.as_ref().map(|r| r.chars.clone()).unwrap_or(String::new());
+        let (before, matched, after) = match hit {
+            Some(hit) => (
+                hit.before.clone(),
+                hit.matched.clone().unwrap_or(chars),
+                hit.after.clone(),
+            ),
+            None => (String::new(), chars, String::new()),
+        };
+        ContentSearchHit { canvas, xywh, before, matched, after }
+    }
+}
+
+#[derive(Deserialize)]
+struct Chars {
+    #[serde(default)]
+    chars: String,
+}
+
+/// Annotationの対象
+/// `canvas#xywh=x,y,w,h` か SpecificResource
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Target {
+    Url(String),
+    SpecificResource { full: String, selector: Option<Selector> },
+}
+
+#[derive(Deserialize)]
+struct Selector {
+    value: String,
+}
+
+impl Target {
+    fn canvas_and_xywh(&self) -> (String, Option<(u32, u32, u32, u32)>) {
+        match self {
+            Target::Url(url) => {
+                let mut split = url.splitn(2, '#');
+                let canvas = split.next().unwrap_or("").to_string();
+                (canvas, split.next().and_then(parse_xywh))
+            }
+            Target::SpecificResource { full, selector } => {
+                (full.clone(), selector.as_ref().and_then(|s| parse_xywh(&s.value)))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Hit {
+    #[serde(default)]
+    annotations: Vec<String>,
+    #[serde(rename = "match", default)]
+    matched: Option<String>,
+    #[serde(default)]
+    before: String,
+    #[serde(default)]
+    after: String,
+}
+
+#[derive(Deserialize)]
+struct TermList {
+    #[serde(default)]
+    terms: Vec<Term>,
+}
+
+/// `xywh=x,y,w,h`を読む
+fn parse_xywh(fragment: &str) -> Option<(u32, u32, u32, u32)> {
+    let values = fragment.trim_start_matches("xywh=")
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().ok().map(|v| v as u32))
+        .collect::<Option<Vec<u32>>>()?;
+    match values.as_slice() {
+        [x, y, w, h] => Some((*x, *y, *w, *h)),
+        _ => None,
+    }
+}
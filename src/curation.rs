@@ -1,10 +1,248 @@
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 
 use crate::viewer::{Position, log, Canvas};
 use std::ops::{Range, RangeInclusive};
+use std::collections::BTreeMap;
 
-use web_sys::{MouseEvent, HtmlImageElement, HtmlCanvasElement, CanvasRenderingContext2d, Element, Node};
+use web_sys::{MouseEvent, HtmlImageElement, HtmlCanvasElement, CanvasRenderingContext2d, Element};
 use crate::iiif_manifest::{Manifest, Image};
+use crate::image_api::{ImageRequest, Region, Size};
+use crate::storage::{CurationLibrary, LocalStorage};
+use self::history::{History, Command};
+use self::arrange::{Filter, SortKey};
+use self::session::Session;
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use self::arrange::{arrange, groups, Group};
+    use crate::storage::MemoryStorage;
+
+    /// テスト用のitem
+    pub fn item(label: &str) -> CurationItem {
+        let json = format!(r#"{{"manifest_id":"m","image_id":"i","label":"{}","crop":[{{"start":0,"end":10}},{{"start":0,"end":10}}],"description":""}}"#, label);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn labels(items: &Vec<CurationItem>) -> Vec<String> {
+        items.iter().map(|i| i.label.clone()).collect()
+    }
+
+    #[test]
+    fn region_url_test() {
+        let mut item = item("a");
+        item.image_id = "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/full/512,/0/native.jpg".to_string();
+        item.crop = (128..=384, 0..=256);
+        assert_eq!(item.region_url(), None);
+
+        item.source_width = Some(512);
+        item.source_height = Some(1024);
+        assert_eq!(item.region_url().unwrap(), "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/pct:25,0,50,25/full/0/default.jpg");
+
+        item.image_id = "https://example.com/iiif/1/full/full/0/default.jpg".to_string();
+        item.source_width = None;
+        assert_eq!(item.region_url().unwrap(), "https://example.com/iiif/1/128,0,256,256/full/0/default.jpg");
+
+        item.image_id = "https://example.com/image.jpg".to_string();
+        assert_eq!(item.region_url(), None);
+        item.service = Some("https://example.com/iiif/2".to_string());
+        item.source_width = Some(512);
+        assert_eq!(item.region_url().unwrap(), "https://example.com/iiif/2/pct:25,0,50,25/full/0/default.jpg");
+
+        // 幅か高さが0の範囲は切り取れない
+        item.crop = (128..=128, 0..=256);
+        assert!(item.is_empty());
+        assert_eq!(item.region_url(), None);
+    }
+
+    #[test]
+    fn json_round_trip_test() {
+        let mut item = item("a");
+        item.notes = "wave motif".to_string();
+        item.add_tag("motif".to_string());
+        item.add_tag("technique".to_string());
+        item.set_metadata("character".to_string(), "fisherman".to_string());
+        item.created = Some(1565000000000.0);
+        item.author = Some("hokusai".to_string());
+
+        let json = item.json().unwrap();
+        let read: CurationItem = serde_json::from_str(&json).unwrap();
+        assert!(read == item);
+        assert_eq!(read.tags(), r#"["motif","technique"]"#);
+        assert_eq!(read.metadata("character".to_string()).unwrap(), "fisherman");
+        assert_eq!(read.created(), Some(1565000000000.0));
+        assert_eq!(read.author().unwrap(), "hokusai");
+
+        // 追加した項目の無い古いJSONも読める
+        let old = r#"{"manifest_id":"m","image_id":"i","label":"a","crop":[{"start":0,"end":10},{"start":0,"end":10}],"description":"d"}"#;
+        let read: CurationItem = serde_json::from_str(old).unwrap();
+        assert_eq!(read.description(), "d");
+        assert_eq!(read.notes(), "");
+        assert_eq!(read.tags(), "[]");
+        assert_eq!(read.metadata_json(), "{}");
+        assert_eq!(read.created(), None);
+        assert_eq!(read.author(), None);
+    }
+
+    #[test]
+    fn undo_redo_test() {
+        let mut history = History::new();
+        let mut items = vec![item("a"), item("b")];
+
+        history.execute(Command::Insert { index: 2, item: item("c") }, &mut items);
+        history.execute(Command::Move { from: 2, to: 0 }, &mut items);
+        history.execute(Command::Remove { index: 1, item: items[1].clone() }, &mut items);
+        assert_eq!(labels(&items), vec!["c", "b"]);
+
+        assert!(history.undo(&mut items));
+        assert!(history.undo(&mut items));
+        assert_eq!(labels(&items), vec!["a", "b", "c"]);
+        assert!(history.redo(&mut items));
+        assert_eq!(labels(&items), vec!["c", "a", "b"]);
+
+        history.execute(Command::Description { index: 0, old: String::new(), new: "desc".to_string() }, &mut items);
+        assert!(!history.can_redo());
+        assert!(history.undo(&mut items));
+        assert_eq!(items[0].description, "");
+
+        let mut tagged = items[0].clone();
+        tagged.add_tag("motif".to_string());
+        history.execute(Command::Replace { index: 0, old: items[0].clone(), new: tagged }, &mut items);
+        assert!(items[0].has_tag("motif".to_string()));
+        assert!(history.undo(&mut items));
+        assert!(items[0].tags.is_empty());
+    }
+
+    #[test]
+    fn dirty_and_limit_test() {
+        let mut history = History::new();
+        let mut items = vec![item("a")];
+        history.set_limit(2);
+        assert!(!history.is_dirty());
+
+        history.execute(Command::Crop { index: 0, old: items[0].crop.clone(), new: (1..=2, 3..=4) }, &mut items);
+        assert!(history.is_dirty());
+        history.mark_saved();
+        assert!(!history.is_dirty());
+
+        history.execute(Command::Insert { index: 1, item: item("b") }, &mut items);
+        history.execute(Command::Insert { index: 2, item: item("c") }, &mut items);
+        assert!(history.undo(&mut items));
+        assert!(history.undo(&mut items));
+        assert!(!history.undo(&mut items));
+        assert!(!history.is_dirty());
+        assert_eq!(items[0].crop, (1..=2, 3..=4));
+    }
+
+    #[test]
+    fn autosave_test() {
+        let mut session = Session::new(MemoryStorage::new());
+        session.library.save(UNTITLED, &vec![item("x")]).unwrap();
+        let mut items = vec![item("a")];
+
+        // 取り消すものが無ければ保存先に書き込まない
+        assert!(!session.undo(&mut items));
+        assert_eq!(session.library.names(), vec![UNTITLED]);
+
+        // 自動保存しても変更ありのままで、既存のUntitledは上書きしない
+        session.execute(Command::Insert { index: 1, item: item("b") }, &mut items);
+        assert!(session.history.is_dirty());
+        assert_eq!(session.name.as_ref().unwrap(), "Untitled (2)");
+        assert_eq!(labels(&session.library.load(UNTITLED).unwrap()), vec!["x"]);
+        assert_eq!(labels(&session.library.load("Untitled (2)").unwrap()), vec!["a", "b"]);
+
+        let name = session.name_or_untitled();
+        session.save_as(name, &items).unwrap();
+        assert!(!session.history.is_dirty());
+
+        assert!(session.undo(&mut items));
+        assert!(session.history.is_dirty());
+        assert_eq!(labels(&session.library.load("Untitled (2)").unwrap()), vec!["a"]);
+        assert!(session.redo(&mut items));
+        assert!(!session.history.is_dirty());
+        assert_eq!(labels(&session.library.load("Untitled (2)").unwrap()), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn crop_history_test() {
+        let mut history = History::new();
+        let mut a = item("a");
+        a.image_id = "https://example.com/iiif/1/full/full/0/default.jpg".to_string();
+        let mut items = vec![a];
+        let url = |items: &Vec<CurationItem>| items[0].region_url().unwrap();
+        assert_eq!(url(&items), "https://example.com/iiif/1/0,0,10,10/full/0/default.jpg");
+
+        // 範囲の変更と取り消し、やり直しに切り取った画像が追従する
+        history.execute(Command::Crop { index: 0, old: items[0].crop.clone(), new: (5..=25, 5..=15) }, &mut items);
+        assert_eq!(url(&items), "https://example.com/iiif/1/5,5,20,10/full/0/default.jpg");
+        assert!(items[0].image.is_none());
+        assert!(history.undo(&mut items));
+        assert_eq!(url(&items), "https://example.com/iiif/1/0,0,10,10/full/0/default.jpg");
+        assert!(history.redo(&mut items));
+        assert_eq!(url(&items), "https://example.com/iiif/1/5,5,20,10/full/0/default.jpg");
+
+        let mut moved = items[0].clone();
+        moved.crop = (0..=30, 0..=30);
+        history.execute(Command::Replace { index: 0, old: items[0].clone(), new: moved }, &mut items);
+        assert_eq!(url(&items), "https://example.com/iiif/1/0,0,30,30/full/0/default.jpg");
+        assert!(history.undo(&mut items));
+        assert_eq!(url(&items), "https://example.com/iiif/1/5,5,20,10/full/0/default.jpg");
+    }
+
+    fn arranged_items() -> Vec<CurationItem> {
+        let item = |manifest_id: &str, label: &str, tags: &[&str], created: f64| {
+            let mut item = item(label);
+            item.manifest_id = manifest_id.to_string();
+            item.tags = tags.iter().map(|t| t.to_string()).collect();
+            item.created = Some(created);
+            item
+        };
+        vec![
+            item("m1", "Wave", &["motif"], 3.0),
+            item("m2", "Fuji", &["motif", "landscape"], 1.0),
+            item("m1", "Boat", &[], 2.0),
+            item("m2", "wave detail", &["technique"], 4.0),
+            item("m1", "boat", &[], 5.0),
+        ]
+    }
+
+    #[test]
+    fn filter_test() {
+        let items = arranged_items();
+        let mut filter = Filter::default();
+        filter.text = Some("WAVE".to_string());
+        assert_eq!(arrange(&items, &filter, SortKey::None, false, false), vec![0, 3]);
+
+        let filter = Filter { tag: Some("motif".to_string()), manifest_id: Some("m2".to_string()), ..Filter::default() };
+        assert_eq!(arrange(&items, &filter, SortKey::None, false, false), vec![1]);
+    }
+
+    #[test]
+    fn sort_and_group_test() {
+        let items = arranged_items();
+        let filter = Filter::default();
+        assert_eq!(arrange(&items, &filter, SortKey::Label, false, false), vec![2, 4, 1, 0, 3]);
+        assert_eq!(arrange(&items, &filter, SortKey::None, true, false), vec![4, 3, 2, 1, 0]);
+        assert_eq!(arrange(&items, &filter, SortKey::Created, true, false), vec![4, 3, 0, 2, 1]);
+        assert_eq!(arrange(&items, &filter, SortKey::Created, false, true), vec![1, 3, 2, 0, 4]);
+        assert_eq!("created".parse::<SortKey>(), Ok(SortKey::Created));
+        assert!("size".parse::<SortKey>().is_err());
+
+        let view = arrange(&items, &filter, SortKey::None, false, false);
+        let groups = groups(&items, &view);
+        assert_eq!(groups[0], Group { manifest_id: "m1".to_string(), indices: vec![0, 2, 4] });
+        assert_eq!(groups[1], Group { manifest_id: "m2".to_string(), indices: vec![1, 3] });
+    }
+}
+
+/// 切り取り範囲(x, y)
+pub type Crop = (RangeInclusive<u32>, RangeInclusive<u32>);
+
+/// 幅か高さが0の切り取り範囲か否か
+fn is_empty_crop(crop: &Crop) -> bool {
+    crop.0.end() <= crop.0.start() || crop.1.end() <= crop.1.start()
+}
 
 #[wasm_bindgen]
 #[derive(Serialize, Deserialize, Clone)]
@@ -18,9 +256,33 @@ pub struct CurationItem {
     /// label
     label: String,
     /// 切り取り
-    crop: (RangeInclusive<u32>, RangeInclusive<u32>),
+    crop: Crop,
+    /// 切り取ったときの画像の大きさ(px)
+    #[serde(default)]
+    source_width: Option<u32>,
+    #[serde(default)]
+    source_height: Option<u32>,
+    /// imageのImage APIのservice(@id)
+    /// Noneならimage_idから推測する
+    #[serde(default)]
+    service: Option<String>,
     /// 説明
     description: String,
+    /// メモ
+    #[serde(default)]
+    notes: String,
+    /// タグ(人物、モチーフ、技法など)
+    #[serde(default)]
+    tags: Vec<String>,
+    /// 任意のkey/value
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    /// 作成日時(UNIX時間, ms)
+    #[serde(default)]
+    created: Option<f64>,
+    /// 作成者
+    #[serde(default)]
+    author: Option<String>,
     #[serde(skip)]
     pub position_x: f64,
     #[serde(skip)]
@@ -40,6 +302,11 @@ impl PartialEq for CurationItem {
             && self.label == other.label
             && self.crop == other.crop
             && self.description == other.description
+            && self.notes == other.notes
+            && self.tags == other.tags
+            && self.metadata == other.metadata
+            && self.created == other.created
+            && self.author == other.author
             && self.position_x == other.position_x
             && self.position_y == other.position_y
             && self.original_x == other.original_x
@@ -71,7 +338,15 @@ impl CurationItem {
             image_id,
             label,
             crop,
+            source_width: Some(img.natural_width()),
+            source_height: Some(img.natural_height()),
+            service: None,
             description,
+            notes: String::new(),
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+            created: Some(js_sys::Date::now()),
+            author: None,
             position_x: 0.0,
             position_y: 0.0,
             original_x: 0.0,
@@ -112,8 +387,67 @@ impl CurationItem {
         self.description.clone()
     }
 
-    pub fn set_description(&mut self, description: String) {
-        self.description = description;
+    pub fn notes(&self) -> String {
+        self.notes.clone()
+    }
+
+    pub fn set_notes(&mut self, notes: String) {
+        self.notes = notes;
+    }
+
+    /// タグ(JSON配列)
+    pub fn tags(&self) -> String {
+        serde_json::to_string(&self.tags).unwrap_or(String::new())
+    }
+
+    pub fn has_tag(&self, tag: String) -> bool {
+        self.tags.contains(&tag)
+    }
+
+    /// タグを追加する
+    /// 既にあれば何もしない
+    pub fn add_tag(&mut self, tag: String) -> bool {
+        let tag = tag.trim().to_string();
+        if tag.is_empty() || self.tags.contains(&tag) {
+            return false;
+        }
+        self.tags.push(tag);
+        true
+    }
+
+    pub fn remove_tag(&mut self, tag: String) -> bool {
+        let len = self.tags.len();
+        self.tags.retain(|t| t != &tag);
+        self.tags.len() != len
+    }
+
+    pub fn metadata(&self, key: String) -> Option<String> {
+        self.metadata.get(&key).cloned()
+    }
+
+    /// metadataのkey/value(JSONオブジェクト)
+    pub fn metadata_json(&self) -> String {
+        serde_json::to_string(&self.metadata).unwrap_or(String::new())
+    }
+
+    pub fn set_metadata(&mut self, key: String, value: String) {
+        self.metadata.insert(key, value);
+    }
+
+    pub fn remove_metadata(&mut self, key: String) -> bool {
+        self.metadata.remove(&key).is_some()
+    }
+
+    pub fn created(&self) -> Option<f64> {
+        self.created
+    }
+
+    pub fn author(&self) -> Option<String> {
+        self.author.clone()
+    }
+
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
     }
 
     pub fn json(&self) -> Option<String> {
@@ -123,6 +457,115 @@ impl CurationItem {
     pub fn set_image(&mut self, image: HtmlImageElement) {
         self.image = Some(image);
     }
+
+    /// 切り取り範囲の幅か高さが0か否か
+    pub fn is_empty(&self) -> bool {
+        is_empty_crop(&self.crop)
+    }
+
+    pub fn set_service(&mut self, service: Option<String>) {
+        self.service = service;
+    }
+
+    /// 切り取った範囲を取得するImage APIのURL
+    /// Image APIから取得できなければNone
+    pub fn region_url(&self) -> Option<String> {
+        self.region_request().map(|request| request.to_string())
+    }
+}
+
+impl CurationItem {
+    /// 切り取り範囲を変え、前の範囲で用意した画像を捨てる
+    fn set_crop(&mut self, crop: Crop) {
+        self.crop = crop;
+        self.image = None;
+    }
+
+    /// 用意した画像を持たない複製(範囲などが変わっているかもしれないため)
+    fn without_image(&self) -> CurationItem {
+        CurationItem { image: None, ..self.clone() }
+    }
+
+    /// 切り取った範囲のImage APIのリクエスト
+    fn region_request(&self) -> Option<ImageRequest> {
+        if self.is_empty() {
+            return None;
+        }
+        let source = self.image_id.parse::<ImageRequest>().ok();
+        // 既に一部を切り取った画像なら範囲が分からない
+        if let Some(source) = &source {
+            if source.region != Region::Full {
+                return None;
+            }
+        }
+        let service = self.service.clone().or(source.as_ref().map(|s| s.service.clone()))?;
+
+        let (x, y) = (*self.crop.0.start(), *self.crop.1.start());
+        let (w, h) = (self.crop.0.end() - x, self.crop.1.end() - y);
+        let region = match (self.source_width, self.source_height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                let (width, height) = (width as f64 / 100.0, height as f64 / 100.0);
+                Region::Percent(x as f64 / width, y as f64 / height, w as f64 / width, h as f64 / height)
+            }
+            // 大きさが分からなければ、原寸の画像のときだけpxで指定できる
+            _ => match source.map(|s| s.size) {
+                Some(Size::Full) | Some(Size::Max) => Region::Pixel(x, y, w, h),
+                _ => return None,
+            }
+        };
+        Some(ImageRequest::new(&service).region(region))
+    }
+
+    /// 切り取った画像を用意する
+    fn render(&mut self) -> HtmlImageElement {
+        if let Some(image) = &self.image {
+            return image.clone();
+        }
+        let image = HtmlImageElement::new().unwrap();
+        match self.region_url() {
+            Some(url) => image.set_src(&url),
+            None => self.crop_on_canvas(&image),
+        }
+        self.image = Some(image.clone());
+        image
+    }
+
+    /// 元の画像を読み込み、canvasで切り取ってimageに設定する
+    fn crop_on_canvas(&self, image: &HtmlImageElement) {
+        if self.is_empty() {
+            return;
+        }
+        let source = HtmlImageElement::new().unwrap();
+        source.set_cross_origin(Some("Anonymous"));
+
+        let (x, y) = (*self.crop.0.start() as f64, *self.crop.1.start() as f64);
+        let (w, h) = (*self.crop.0.end() as f64 - x, *self.crop.1.end() as f64 - y);
+        let loaded = source.clone();
+        let image = image.clone();
+        // 読み込み後に一度だけ呼ばれ、呼ばれたら解放される
+        let onload = Closure::once_into_js(move || {
+            let document = web_sys::window().unwrap().document().unwrap();
+            let canvas = match document.create_element("canvas")
+                .map(|e| e.dyn_into::<HtmlCanvasElement>()) {
+                Ok(Ok(canvas)) => canvas,
+                _ => return,
+            };
+            canvas.set_width(w as u32);
+            canvas.set_height(h as u32);
+            let context = match canvas.get_context("2d")
+                .map(|c| c.map(|c| c.dyn_into::<CanvasRenderingContext2d>())) {
+                Ok(Some(Ok(context))) => context,
+                _ => return,
+            };
+            let _ = context.draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                &loaded, x, y, w, h, 0.0, 0.0, w, h);
+            if let Ok(url) = canvas.to_data_url() {
+                image.set_src(&url);
+            }
+        });
+        source.set_onload(Some(onload.unchecked_ref()));
+        source.set_src(&self.image_id);
+    }
 }
 
 //impl From<Vec<CurationItem>> for Manifest {
@@ -136,10 +579,27 @@ impl CurationItem {
 //    }
 //}
 
+/// 名前を付けていないキュレーションを自動保存する名前
+const UNTITLED: &str = "Untitled";
+
 #[wasm_bindgen]
 pub struct WasmCurationViewer {
     canvas: Canvas,
     items: Vec<CurationItem>,
+    /// 編集履歴と保存先
+    session: Session<LocalStorage>,
+    /// 追加するitemの作成者
+    author: Option<String>,
+    /// 絞り込みの条件
+    filter: Filter,
+    /// 並べ替えの基準
+    sort: SortKey,
+    /// 降順に並べるか否か
+    descending: bool,
+    /// Manifestごとにまとめるか否か
+    group_by_manifest: bool,
+    /// 絞り込み・並べ替えた結果(itemsのindex)
+    view: Vec<usize>,
     pub index: usize,
 }
 
@@ -150,6 +610,13 @@ impl WasmCurationViewer {
         Self {
             canvas: Canvas::new(element),
             items: Vec::new(),
+            session: Session::new(LocalStorage::new()),
+            author: None,
+            filter: Filter::default(),
+            sort: SortKey::None,
+            descending: false,
+            group_by_manifest: false,
+            view: Vec::new(),
             index: 0,
         }
     }
@@ -161,6 +628,8 @@ impl WasmCurationViewer {
         };
 
         self.items = items;
+        self.session.reset(None);
+        self.refresh();
         true
     }
 
@@ -188,26 +657,236 @@ impl WasmCurationViewer {
         self.items.get(self.index).cloned()
     }
 
-    pub fn push(&mut self, item: &CurationItem) {
-        self.items.push(item.clone());
+    /// itemを追加する
+    /// 切り取り範囲が空なら追加しない
+    pub fn push(&mut self, item: &CurationItem) -> bool {
+        if item.is_empty() {
+            log("Cannot add an empty crop");
+            return false;
+        }
+        let index = self.items.len();
+        let mut item = item.clone();
+        if item.author.is_none() {
+            item.author = self.author.clone();
+        }
+        self.execute(Command::Insert { index, item });
+        true
+    }
+
+    /// 以降に追加するitemの作成者を設定する
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
+    }
+
+    /// itemを編集したものに置き換える
+    /// タグやmetadataなどの変更はこれを通すと取り消せる
+    pub fn update(&mut self, index: usize, item: &CurationItem) -> bool {
+        let old = match self.items.get(index) {
+            Some(old) => old.clone(),
+            None => return false,
+        };
+        if &old == item {
+            return false;
+        }
+
+        self.execute(Command::Replace { index, old, new: item.clone() });
+        true
+    }
+
+    /// 使われているタグ(JSON配列)
+    pub fn tags(&self) -> String {
+        let mut tags: Vec<&String> = self.items.iter().flat_map(|item| &item.tags).collect();
+        tags.sort();
+        tags.dedup();
+        serde_json::to_string(&tags).unwrap_or(String::new())
+    }
+
+    /// タグの付いたitemのindex
+    pub fn indices_by_tag(&self, tag: String) -> Vec<u32> {
+        self.indices_by(|item| item.tags.contains(&tag))
+    }
+
+    /// metadataのkeyの値がvalueであるitemのindex
+    pub fn indices_by_metadata(&self, key: String, value: String) -> Vec<u32> {
+        self.indices_by(|item| item.metadata.get(&key) == Some(&value))
     }
 
-    pub fn remove(&mut self, index: usize) {
-        self.items.remove(index);
+    /// 作成者がauthorであるitemのindex
+    pub fn indices_by_author(&self, author: String) -> Vec<u32> {
+        self.indices_by(|item| item.author.as_ref() == Some(&author))
+    }
+
+    pub fn remove(&mut self, index: usize) -> bool {
+        let item = match self.items.get(index) {
+            Some(item) => item.clone(),
+            None => return false,
+        };
+        self.execute(Command::Remove { index, item });
+        true
     }
 
     pub fn swap(&mut self, oldindex: usize, newindex: usize) -> bool {
-        let items = &mut self.items;
+        let items = &self.items;
         if items.get(oldindex) == None || items.get(newindex) == None {
             return false;
         }
 
-        let item = items.remove(oldindex);
-        items.insert(newindex, item);
+        self.execute(Command::Move { from: oldindex, to: newindex });
+        true
+    }
+
+    /// itemの説明を変更する
+    pub fn set_description(&mut self, index: usize, description: String) -> bool {
+        let old = match self.items.get(index) {
+            Some(item) => item.description.clone(),
+            None => return false,
+        };
+        if old == description {
+            return false;
+        }
+
+        self.execute(Command::Description { index, old, new: description });
+        true
+    }
+
+    /// itemの切り取り範囲を変更する
+    pub fn set_crop(&mut self, index: usize, x_start: u32, x_end: u32, y_start: u32, y_end: u32) -> bool {
+        let old = match self.items.get(index) {
+            Some(item) => item.crop.clone(),
+            None => return false,
+        };
+        let new = (x_start..=x_end, y_start..=y_end);
+        if old == new || is_empty_crop(&new) {
+            return false;
+        }
+
+        self.execute(Command::Crop { index, old, new });
+        true
+    }
+
+    /// 直前の操作を取り消す
+    pub fn undo(&mut self) -> bool {
+        let done = self.session.undo(&mut self.items);
+        self.refresh();
+        done
+    }
+
+    /// 取り消した操作をやり直す
+    pub fn redo(&mut self) -> bool {
+        let done = self.session.redo(&mut self.items);
+        self.refresh();
+        done
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.session.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.session.history.can_redo()
+    }
+
+    /// 最後に保存してから変更があったか否か
+    pub fn is_dirty(&self) -> bool {
+        self.session.history.is_dirty()
+    }
+
+    /// 現在の状態を保存済みとする
+    pub fn mark_saved(&mut self) {
+        self.session.history.mark_saved();
+    }
+
+    /// 履歴に残す操作の上限を設定する
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.session.history.set_limit(limit);
+    }
+
+    /// 開いているキュレーションの名前
+    pub fn name(&self) -> Option<String> {
+        self.session.name.clone()
+    }
+
+    /// 保存されているキュレーションの名前(JSON配列)
+    pub fn curation_names(&self) -> String {
+        serde_json::to_string(&self.session.library.names()).unwrap_or(String::new())
+    }
+
+    pub fn set_autosave(&mut self, autosave: bool) {
+        self.session.autosave = autosave;
+    }
+
+    /// 開いているキュレーションを保存する
+    /// 名前が無ければ既存のものと重ならない`Untitled`の名前を付ける
+    pub fn save(&mut self) -> bool {
+        let name = self.session.name_or_untitled();
+        self.save_as(name)
+    }
+
+    /// 名前を付けて保存する
+    pub fn save_as(&mut self, name: String) -> bool {
+        match self.session.save_as(name.clone(), &self.items) {
+            Ok(_) => true,
+            Err(e) => {
+                log(&format!("Cannot save curation {}: {:?}", name, e));
+                false
+            }
+        }
+    }
+
+    /// 保存されたキュレーションを開く
+    pub fn open(&mut self, name: String) -> bool {
+        let items = match self.session.library.load(&name) {
+            Ok(items) => items,
+            Err(e) => {
+                log(&format!("Cannot open curation {}: {:?}", name, e));
+                return false;
+            }
+        };
 
+        self.items = items;
+        self.index = 0;
+        self.refresh();
+        let _ = self.session.library.set_last_opened(&name);
+        self.session.reset(Some(name));
+        true
+    }
+
+    /// 最後に開いたキュレーションを開く
+    pub fn restore(&mut self) -> bool {
+        match self.session.library.last_opened() {
+            Some(name) => self.open(name),
+            None => false,
+        }
+    }
+
+    /// 保存されたキュレーションを削除する
+    pub fn delete_curation(&mut self, name: String) -> bool {
+        if let Err(e) = self.session.library.delete(&name) {
+            log(&format!("Cannot delete curation {}: {:?}", name, e));
+            return false;
+        }
+        if self.session.name.as_ref() == Some(&name) {
+            self.session.name = None;
+        }
         true
     }
 
+    /// すべてのキュレーションを1つのJSONに書き出す
+    pub fn export_library(&self) -> Option<String> {
+        self.session.library.export().ok()
+    }
+
+    /// 書き出したJSONからキュレーションを読み込む
+    pub fn import_library(&mut self, json: String) -> bool {
+        match self.session.library.import(&json) {
+            Ok(_) => true,
+            Err(e) => {
+                log(&format!("Cannot import curations: {:?}", e));
+                false
+            }
+        }
+    }
+
     pub fn json(&self) -> Option<String> {
         serde_json::to_string(&self.items).ok()
     }
@@ -223,23 +902,18 @@ impl WasmCurationViewer {
                 }
             }
         }
-        if let Some(image) = self.items.get(index) {
-            if let Some(img) = &image.image {
-                self.index = index;
-                self.canvas.element.append_child(&Node::from(Element::from(img.clone())));
-            }
-        }
+        self.show_by_index(index);
         index
     }
 
     #[wasm_bindgen]
     /// イメージを表示する
+    /// 切り取った画像が無ければImage APIかcanvasで用意する
     pub fn show_by_index(&mut self, index: usize) {
-        if let Some(image) = self.items.get(index) {
-            if let Some(img) = &image.image {
-                self.index = index;
-                self.canvas.element.append_child(&Node::from(Element::from(img.clone())));
-            }
+        if let Some(item) = self.items.get_mut(index) {
+            let img = item.render();
+            self.index = index;
+            self.canvas.show(&img);
         }
     }
 
@@ -262,19 +936,88 @@ impl WasmCurationViewer {
     #[wasm_bindgen]
     /// 次のイメージを表示する
     pub fn next(&mut self) {
-        self.show_by_index(self.index + 1)
+        if let Some(index) = self.neighbor(1) {
+            self.show_by_index(index)
+        }
     }
 
     #[wasm_bindgen]
     /// 前のイメージを表示する
     pub fn prev(&mut self) {
-        self.show_by_index(self.index - 1)
+        if let Some(index) = self.neighbor(-1) {
+            self.show_by_index(index)
+        }
     }
 
     /// 最後のイメージを表示する
     pub fn show_last(&mut self) {
-        let index = self.items.len();
-        self.show_by_index(index - 1);
+        if let Some(&index) = self.view.last() {
+            self.show_by_index(index);
+        }
+    }
+
+    /// 表示する順番に並べたitemのindex
+    pub fn view_indices(&self) -> Vec<u32> {
+        self.view.iter().map(|&i| i as u32).collect()
+    }
+
+    /// 絞り込んだitemの数
+    pub fn view_size(&self) -> usize {
+        self.view.len()
+    }
+
+    /// 現在のitemが表示する順番で何番目か
+    pub fn position(&self) -> Option<usize> {
+        self.view.iter().position(|&i| i == self.index)
+    }
+
+    /// label・説明に含まれる文字列で絞り込む
+    pub fn set_filter_text(&mut self, text: Option<String>) {
+        self.filter.text = text.filter(|t| !t.trim().is_empty());
+        self.refresh();
+    }
+
+    /// タグで絞り込む
+    pub fn set_filter_tag(&mut self, tag: Option<String>) {
+        self.filter.tag = tag;
+        self.refresh();
+    }
+
+    /// 取得元のManifestで絞り込む
+    pub fn set_filter_manifest(&mut self, manifest_id: Option<String>) {
+        self.filter.manifest_id = manifest_id;
+        self.refresh();
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.filter = Filter::default();
+        self.refresh();
+    }
+
+    /// 並べ替える
+    /// keyは"none", "label", "source", "created"のいずれか
+    /// "none"の降順は追加した順の逆
+    pub fn set_sort(&mut self, key: String, descending: bool) -> bool {
+        let sort = match key.parse() {
+            Ok(sort) => sort,
+            Err(_) => return false,
+        };
+        self.sort = sort;
+        self.descending = descending;
+        self.refresh();
+        true
+    }
+
+    /// Manifestごとにまとめる
+    pub fn set_group_by_manifest(&mut self, group: bool) {
+        self.group_by_manifest = group;
+        self.refresh();
+    }
+
+    /// Manifestごとのitemのindex(JSON配列)
+    /// `[{"manifest_id": "...", "indices": [0, 2]}, ...]`
+    pub fn groups(&self) -> String {
+        serde_json::to_string(&arrange::groups(&self.items, &self.view)).unwrap_or(String::new())
     }
 
     /// mousedownイベント
@@ -308,3 +1051,404 @@ impl WasmCurationViewer {
         self.canvas.mousedown = None;
     }
 }
+
+impl WasmCurationViewer {
+    /// 操作を実行して履歴に積む
+    fn execute(&mut self, command: Command) {
+        self.session.execute(command, &mut self.items);
+        self.refresh();
+    }
+
+    /// 条件に合うitemのindex
+    fn indices_by<F: Fn(&CurationItem) -> bool>(&self, f: F) -> Vec<u32> {
+        self.items.iter().enumerate()
+            .filter(|(_, item)| f(item))
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    /// indexがitemsの範囲に収まるようにし、表示する順番を作り直す
+    fn refresh(&mut self) {
+        if self.index >= self.items.len() {
+            self.index = self.items.len().saturating_sub(1);
+        }
+        self.view = arrange::arrange(&self.items, &self.filter, self.sort, self.descending, self.group_by_manifest);
+    }
+
+    /// 表示する順番でindexからstep個離れたitemのindex
+    fn neighbor(&self, step: isize) -> Option<usize> {
+        let len = self.view.len() as isize;
+        if len == 0 {
+            return None;
+        }
+        match self.view.iter().position(|&i| i == self.index) {
+            Some(position) => {
+                let position = position as isize + step;
+                if 0 <= position && position < len {
+                    Some(self.view[position as usize])
+                } else { None }
+            }
+            // 現在のitemが絞り込みで外れているときは端から
+            None => if step > 0 { self.view.first().cloned() } else { self.view.last().cloned() },
+        }
+    }
+}
+
+/// itemの絞り込み・並べ替え・グループ化
+pub mod arrange {
+    use super::*;
+    use std::cmp::Ordering;
+    use std::str::FromStr;
+
+    /// 絞り込みの条件
+    /// Noneの条件は無視する
+    #[derive(Default, Clone)]
+    pub struct Filter {
+        pub text: Option<String>,
+        pub tag: Option<String>,
+        pub manifest_id: Option<String>,
+    }
+
+    impl Filter {
+        pub fn matches(&self, item: &CurationItem) -> bool {
+            if let Some(text) = &self.text {
+                let text = text.to_lowercase();
+                if !item.label.to_lowercase().contains(&text)
+                    && !item.description.to_lowercase().contains(&text) {
+                    return false;
+                }
+            }
+            if let Some(tag) = &self.tag {
+                if !item.tags.contains(tag) {
+                    return false;
+                }
+            }
+            if let Some(manifest_id) = &self.manifest_id {
+                if &item.manifest_id != manifest_id {
+                    return false;
+                }
+            }
+            true
+        }
+    }
+
+    /// 並べ替えの基準
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum SortKey {
+        /// 追加した順
+        None,
+        Label,
+        /// 取得元(Manifest, image)
+        Source,
+        Created,
+    }
+
+    impl FromStr for SortKey {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "" | "none" => Ok(SortKey::None),
+                "label" => Ok(SortKey::Label),
+                "source" => Ok(SortKey::Source),
+                "created" => Ok(SortKey::Created),
+                _ => Err(format!("Unknown sort key: {}", s)),
+            }
+        }
+    }
+
+    impl SortKey {
+        fn compare(&self, a: &CurationItem, b: &CurationItem) -> Ordering {
+            match self {
+                SortKey::None => Ordering::Equal,
+                // 大文字と小文字は区別しない(絞り込みと同じ)
+                SortKey::Label => a.label.to_lowercase().cmp(&b.label.to_lowercase())
+                    .then_with(|| a.label.cmp(&b.label)),
+                SortKey::Source => a.manifest_id.cmp(&b.manifest_id)
+                    .then_with(|| a.image_id.cmp(&b.image_id)),
+                SortKey::Created => a.created.partial_cmp(&b.created).unwrap_or(Ordering::Equal),
+            }
+        }
+    }
+
+    /// 絞り込み・並べ替えたitemのindex
+    /// 基準が同じitemは元の順番を保つ
+    /// `SortKey::None`の降順は追加した順の逆(新しい順)
+    pub fn arrange(items: &Vec<CurationItem>, filter: &Filter, sort: SortKey, descending: bool, group_by_manifest: bool) -> Vec<usize> {
+        let mut view: Vec<usize> = (0..items.len())
+            .filter(|&i| filter.matches(&items[i]))
+            .collect();
+        if sort == SortKey::None {
+            if descending {
+                view.reverse();
+            }
+        } else {
+            view.sort_by(|&a, &b| {
+                let ordering = sort.compare(&items[a], &items[b]);
+                if descending { ordering.reverse() } else { ordering }
+            });
+        }
+
+        if group_by_manifest {
+            // Manifestが最初に現れた順にまとめる
+            let mut order: Vec<&String> = Vec::new();
+            for &i in &view {
+                if !order.contains(&&items[i].manifest_id) {
+                    order.push(&items[i].manifest_id);
+                }
+            }
+            view.sort_by_key(|&i| order.iter().position(|m| *m == &items[i].manifest_id));
+        }
+        view
+    }
+
+    /// Manifestごとのitem
+    #[derive(Serialize, Debug, PartialEq)]
+    pub struct Group {
+        pub manifest_id: String,
+        pub indices: Vec<usize>,
+    }
+
+    /// viewの順番を保ったままManifestごとにまとめる
+    pub fn groups(items: &Vec<CurationItem>, view: &Vec<usize>) -> Vec<Group> {
+        let mut groups: Vec<Group> = Vec::new();
+        for &i in view {
+            let manifest_id = &items[i].manifest_id;
+            match groups.iter_mut().find(|g| &g.manifest_id == manifest_id) {
+                Some(group) => group.indices.push(i),
+                None => groups.push(Group { manifest_id: manifest_id.clone(), indices: vec![i] }),
+            }
+        }
+        groups
+    }
+}
+
+/// キュレーション編集の履歴
+pub mod history {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// 履歴に残す操作の既定の上限
+    const DEFAULT_LIMIT: usize = 100;
+
+    /// 取り消し可能な操作
+    #[derive(Clone)]
+    pub enum Command {
+        /// itemを追加
+        Insert { index: usize, item: CurationItem },
+        /// itemを削除
+        Remove { index: usize, item: CurationItem },
+        /// itemを移動
+        Move { from: usize, to: usize },
+        /// 説明を変更
+        Description { index: usize, old: String, new: String },
+        /// 切り取り範囲を変更
+        Crop { index: usize, old: Crop, new: Crop },
+        /// itemを置き換え
+        Replace { index: usize, old: CurationItem, new: CurationItem },
+    }
+
+    impl Command {
+        /// 操作を適用する
+        fn apply(&self, items: &mut Vec<CurationItem>) {
+            match self {
+                Command::Insert { index, item } => items.insert(*index, item.clone()),
+                Command::Remove { index, .. } => { items.remove(*index); }
+                Command::Move { from, to } => {
+                    let item = items.remove(*from);
+                    items.insert(*to, item);
+                }
+                Command::Description { index, new, .. } => items[*index].description = new.clone(),
+                Command::Crop { index, new, .. } => items[*index].set_crop(new.clone()),
+                Command::Replace { index, new, .. } => items[*index] = new.without_image(),
+            }
+        }
+
+        /// 操作を取り消す
+        fn revert(&self, items: &mut Vec<CurationItem>) {
+            match self {
+                Command::Insert { index, .. } => { items.remove(*index); }
+                Command::Remove { index, item } => items.insert(*index, item.clone()),
+                Command::Move { from, to } => {
+                    let item = items.remove(*to);
+                    items.insert(*from, item);
+                }
+                Command::Description { index, old, .. } => items[*index].description = old.clone(),
+                Command::Crop { index, old, .. } => items[*index].set_crop(old.clone()),
+                Command::Replace { index, old, .. } => items[*index] = old.without_image(),
+            }
+        }
+    }
+
+    /// undo/redoのスタック
+    pub struct History {
+        /// 取り消し可能な操作(古い順)
+        undo: VecDeque<(u64, Command)>,
+        /// やり直し可能な操作(新しく取り消した順に末尾)
+        redo: Vec<(u64, Command)>,
+        /// 上限を超えて捨てた最後の操作のid
+        base: u64,
+        /// 次に積む操作のid
+        next_id: u64,
+        /// 保存時点の状態のid
+        saved: u64,
+        limit: usize,
+    }
+
+    impl History {
+        pub fn new() -> Self {
+            Self { undo: VecDeque::new(), redo: Vec::new(), base: 0, next_id: 1, saved: 0, limit: DEFAULT_LIMIT }
+        }
+
+        /// 操作を適用して履歴に積む
+        pub fn execute(&mut self, command: Command, items: &mut Vec<CurationItem>) {
+            command.apply(items);
+            self.redo.clear();
+            self.undo.push_back((self.next_id, command));
+            self.next_id += 1;
+            self.truncate();
+        }
+
+        pub fn undo(&mut self, items: &mut Vec<CurationItem>) -> bool {
+            match self.undo.pop_back() {
+                Some((id, command)) => {
+                    command.revert(items);
+                    self.redo.push((id, command));
+                    true
+                }
+                None => false,
+            }
+        }
+
+        pub fn redo(&mut self, items: &mut Vec<CurationItem>) -> bool {
+            match self.redo.pop() {
+                Some((id, command)) => {
+                    command.apply(items);
+                    self.undo.push_back((id, command));
+                    true
+                }
+                None => false,
+            }
+        }
+
+        pub fn can_undo(&self) -> bool {
+            !self.undo.is_empty()
+        }
+
+        pub fn can_redo(&self) -> bool {
+            !self.redo.is_empty()
+        }
+
+        /// 現在の状態のid
+        fn state(&self) -> u64 {
+            self.undo.back().map(|(id, _)| *id).unwrap_or(self.base)
+        }
+
+        pub fn is_dirty(&self) -> bool {
+            self.state() != self.saved
+        }
+
+        pub fn mark_saved(&mut self) {
+            self.saved = self.state();
+        }
+
+        pub fn set_limit(&mut self, limit: usize) {
+            self.limit = limit;
+            self.truncate();
+        }
+
+        /// 履歴を空にし、現在の状態を保存済みとする
+        pub fn clear(&mut self) {
+            self.undo.clear();
+            self.redo.clear();
+            self.base = self.next_id;
+            self.next_id += 1;
+            self.saved = self.base;
+        }
+
+        /// 上限を超えた古い操作を捨てる
+        fn truncate(&mut self) {
+            while self.undo.len() > self.limit {
+                if let Some((id, _)) = self.undo.pop_front() {
+                    self.base = id;
+                }
+            }
+        }
+    }
+}
+
+/// 編集履歴と保存先
+pub mod session {
+    use super::*;
+    use crate::storage::{Storage, StorageError};
+
+    pub struct Session<S: Storage> {
+        pub history: History,
+        pub library: CurationLibrary<S>,
+        /// 開いているキュレーションの名前
+        pub name: Option<String>,
+        /// 変更のたびに保存先へ書き込むか否か
+        pub autosave: bool,
+    }
+
+    impl<S: Storage> Session<S> {
+        pub fn new(storage: S) -> Self {
+            Self { history: History::new(), library: CurationLibrary::new(storage), name: None, autosave: true }
+        }
+
+        /// 別のキュレーションに切り替え、履歴を空にする
+        pub fn reset(&mut self, name: Option<String>) {
+            self.history.clear();
+            self.name = name;
+        }
+
+        /// 開いているキュレーションの名前
+        /// 名前が無ければ既存のものと重ならない`Untitled`の名前
+        pub fn name_or_untitled(&self) -> String {
+            self.name.clone().unwrap_or_else(|| self.library.unique_name(UNTITLED))
+        }
+
+        /// 名前を付けて保存し、現在の状態を保存済みとする
+        pub fn save_as(&mut self, name: String, items: &Vec<CurationItem>) -> Result<(), StorageError> {
+            self.library.save(&name, items)?;
+            self.name = Some(name);
+            self.history.mark_saved();
+            Ok(())
+        }
+
+        /// 操作を実行して履歴に積む
+        pub fn execute(&mut self, command: Command, items: &mut Vec<CurationItem>) {
+            self.history.execute(command, items);
+            self.autosave(items);
+        }
+
+        pub fn undo(&mut self, items: &mut Vec<CurationItem>) -> bool {
+            let done = self.history.undo(items);
+            if done {
+                self.autosave(items);
+            }
+            done
+        }
+
+        pub fn redo(&mut self, items: &mut Vec<CurationItem>) -> bool {
+            let done = self.history.redo(items);
+            if done {
+                self.autosave(items);
+            }
+            done
+        }
+
+        /// 自動保存が有効なら保存先に書き込む
+        /// 明示的に保存するまでは保存済みとしない
+        fn autosave(&mut self, items: &Vec<CurationItem>) {
+            if !self.autosave {
+                return;
+            }
+            let name = self.name_or_untitled();
+            match self.library.save(&name, items) {
+                Ok(_) => self.name = Some(name),
+                Err(e) => log(&format!("Cannot save curation {}: {:?}", name, e)),
+            }
+        }
+    }
+}
@@ -1,16 +1,21 @@
 use std::str::FromStr;
 
 use crate::viewer::{log, ViewerImage};
+use crate::image_api::{ImageRequest, Size};
 use wasm_bindgen::prelude::*;
 use web_sys::{Element, HtmlLiElement, ElementCreationOptions};
 
+/// 作るthumbnailの大きさ(IconViewで表示する大きさの2倍)
+pub const THUMBNAIL_WIDTH: u32 = 240;
+pub const THUMBNAIL_HEIGHT: u32 = 260;
+
 pub trait ManifestSubstructure {
     fn to_image_list(&self) -> Vec<Element>;
 }
 
 #[cfg(test)]
 mod test {
-    use crate::iiif_manifest::Manifest;
+    use crate::iiif_manifest::{Manifest, Thumbnail};
 
     #[test]
     fn parse_europeana_test() {
@@ -31,6 +36,82 @@ mod test {
         }
         assert!(manifest.is_ok());
     }
+
+    #[test]
+    fn search_service_test() {
+        let json = include_str!("../test/hokusai.json").replacen("\"sequences\"", r#""service": {
+            "@context": "http://iiif.io/api/search/1/context.json",
+            "@id": "https://example.org/search",
+            "profile": "http://iiif.io/api/search/1/search",
+            "service": {
+                "@id": "https://example.org/autocomplete",
+                "profile": "http://iiif.io/api/search/1/autocomplete"
+            }
+        }, "sequences""#, 1);
+        let manifest = serde_json::from_str::<Manifest>(&json).unwrap();
+        let search = manifest.search_service().unwrap();
+        assert_eq!(search.id, "https://example.org/search");
+        assert_eq!(search.autocomplete().unwrap().id, "https://example.org/autocomplete");
+
+        let manifest = serde_json::from_str::<Manifest>(include_str!("../test/hokusai.json")).unwrap();
+        assert!(manifest.search_service().is_none());
+    }
+
+    #[test]
+    fn lenient_metadata_test() {
+        // valueが文字列の配列、または言語付きの値1つでも読める
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../test/hokusai.json")).unwrap();
+        json["metadata"] = serde_json::json!([
+            {"label": "Subject", "value": ["Mount Fuji", "Waves"]},
+            {"label": "Title", "value": {"@value": "Kanagawa oki nami ura", "@language": "ja-Latn"}},
+            {"label": "Pages", "value": 1}
+        ]);
+        let manifest = serde_json::from_value::<Manifest>(json).unwrap();
+        let metadata = manifest.metadata();
+        assert_eq!(metadata[0].value.texts(), vec!["Mount Fuji", "Waves"]);
+        assert_eq!(metadata[1].value.texts(), vec!["Kanagawa oki nami ura"]);
+        assert!(metadata[2].value.texts().is_empty());
+        assert_eq!(metadata[0].label.texts(), vec!["Subject"]);
+    }
+
+    #[test]
+    fn right_to_left_test() {
+        let manifest = serde_json::from_str::<Manifest>(include_str!("../test/hokusai.json")).unwrap();
+        assert!(!manifest.right_to_left());
+
+        let json = include_str!("../test/hokusai.json").replacen("\"sequences\"", r#""viewingDirection": "right-to-left", "sequences""#, 1);
+        let mut manifest = serde_json::from_str::<Manifest>(&json).unwrap();
+        assert!(manifest.right_to_left());
+        manifest.sequences[0].viewing_direction = Some("left-to-right".to_string());
+        assert!(!manifest.right_to_left());
+    }
+
+    #[test]
+    fn thumbnail_test() {
+        // canvasのthumbnailを消す
+        let mut json: serde_json::Value = serde_json::from_str(include_str!("../test/hokusai.json")).unwrap();
+        for canvas in json["sequences"][0]["canvases"].as_array_mut().unwrap() {
+            canvas.as_object_mut().unwrap().remove("thumbnail");
+        }
+        let mut manifest = serde_json::from_value::<Manifest>(json).unwrap();
+        let images = manifest.get_viewer_images();
+        assert_eq!(images[0].service_level, Some(2));
+        assert_eq!(images[0].service.as_ref().unwrap(), "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1");
+        assert_eq!(images[0].thumbnail.as_ref().unwrap(), "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/full/!240,260/0/default.jpg");
+
+        // Image APIが無ければsequenceのthumbnail
+        for canvas in &mut manifest.sequences[0].canvases {
+            for image in &mut canvas.images {
+                image.resource.service = None;
+            }
+        }
+        manifest.sequences[0].thumbnail = Some(Thumbnail { id: "https://example.org/thumbnail.jpg".to_string(), type_: "dctypes:Image".to_string(), format: "image/jpeg".to_string() });
+        assert_eq!(manifest.get_viewer_images()[0].thumbnail.as_ref().unwrap(), "https://example.org/thumbnail.jpg");
+
+        // どちらも無ければ画像を縮小して作る
+        manifest.sequences[0].thumbnail = None;
+        assert!(manifest.get_viewer_images()[0].thumbnail.is_none());
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -43,16 +124,73 @@ pub struct Manifest {
     type_: String,
     pub label: Label,
     license: Option<String>,
-    attribution: Option<String>,
+    attribution: Option<Label>,
     description: Option<Label>,
+    /// label/valueの組
+    #[serde(default)]
+    metadata: Vec<MetadataEntry>,
+    logo: Option<Links>,
+    /// 人が読むための関連ページ
+    related: Option<Links>,
+    /// 機械が読むための関連データ
+    #[serde(rename = "seeAlso")]
+    see_also: Option<Links>,
+    /// PDFなど別の形式
+    rendering: Option<Links>,
+    /// 頁をめくる方向(left-to-right, right-to-leftなど)
+    #[serde(rename = "viewingDirection")]
+    viewing_direction: Option<String>,
+    /// 検索などのサービス
+    service: Option<Services>,
     sequences: Vec<Sequence>,
 }
 
+/// 1つまたは複数のサービス
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Services {
+    One(ManifestService),
+    Many(Vec<ManifestService>),
+}
+
+impl Services {
+    fn iter(&self) -> std::slice::Iter<'_, ManifestService> {
+        match self {
+            Services::One(service) => std::slice::from_ref(service).iter(),
+            Services::Many(services) => services.iter(),
+        }
+    }
+
+    /// profileが当てはまるサービスを探す
+    fn find(&self, f: fn(&str) -> bool) -> Option<&ManifestService> {
+        self.iter().find(|s| s.profile.as_ref().map(|p| f(p)).unwrap_or(false))
+    }
+}
+
+/// Manifestに付いたサービス(Content Searchなど)
+#[derive(Deserialize, Debug, Serialize)]
+pub struct ManifestService {
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub profile: Option<String>,
+    /// 入れ子のサービス(autocompleteなど)
+    service: Option<Box<Services>>,
+}
+
+impl ManifestService {
+    /// Content Searchに付いた自動補完のサービス
+    pub fn autocomplete(&self) -> Option<&ManifestService> {
+        self.service.as_ref()?.find(|p| p.starts_with("http://iiif.io/api/search/") && p.ends_with("/autocomplete"))
+    }
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 #[serde(untagged)]
 pub enum Label {
     String(String),
     Vec(Vec<EuropeanaContent>),
+    /// 文字列の配列、言語付きの値1つなど上に当てはまらない形
+    Other(serde_json::Value),
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -61,19 +199,177 @@ pub struct EuropeanaContent {
     pub value: String
 }
 
+impl Label {
+    /// すべての値
+    pub fn texts(&self) -> Vec<&str> {
+        match self {
+            Label::String(s) => vec![s.as_str()],
+            Label::Vec(vec) => vec.iter().map(|ec| ec.value.as_str()).collect(),
+            Label::Other(value) => {
+                let mut texts = Vec::new();
+                collect_texts(value, &mut texts);
+                texts
+            }
+        }
+    }
+}
+
+/// 文字列と@valueを順に集める
+fn collect_texts<'a>(value: &'a serde_json::Value, texts: &mut Vec<&'a str>) {
+    match value {
+        serde_json::Value::String(s) => texts.push(s),
+        serde_json::Value::Array(values) => values.iter().for_each(|v| collect_texts(v, texts)),
+        serde_json::Value::Object(map) => {
+            if let Some(v) = map.get("@value") {
+                collect_texts(v, texts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 外部のリソース(URLのみ、またはlabelなどを持つ)
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Link {
+    Url(String),
+    Resource {
+        #[serde(rename = "@id")]
+        id: String,
+        label: Option<Label>,
+        format: Option<String>,
+    },
+}
+
+impl Link {
+    pub fn id(&self) -> &str {
+        match self {
+            Link::Url(id) => id,
+            Link::Resource { id, .. } => id,
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Link::Url(_) => None,
+            Link::Resource { label, .. } => label.as_ref()?.texts().into_iter().next(),
+        }
+    }
+
+    pub fn format(&self) -> Option<&str> {
+        match self {
+            Link::Url(_) => None,
+            Link::Resource { format, .. } => format.as_ref().map(String::as_str),
+        }
+    }
+}
+
+/// 1つまたは複数の外部のリソース
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Links {
+    One(Link),
+    Many(Vec<Link>),
+}
+
+impl Links {
+    pub fn iter(&self) -> std::slice::Iter<'_, Link> {
+        match self {
+            Links::One(link) => std::slice::from_ref(link).iter(),
+            Links::Many(links) => links.iter(),
+        }
+    }
+}
+
+/// Manifestのmetadataの1項目
+#[derive(Deserialize, Debug, Serialize)]
+pub struct MetadataEntry {
+    pub label: Label,
+    pub value: Label,
+}
+
 impl Manifest {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn description(&self) -> Option<&Label> {
+        self.description.as_ref()
+    }
+
+    pub fn metadata(&self) -> &Vec<MetadataEntry> {
+        &self.metadata
+    }
+
+    pub fn attribution(&self) -> Option<&Label> {
+        self.attribution.as_ref()
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_ref().map(String::as_str)
+    }
+
+    pub fn logo(&self) -> Option<&Links> {
+        self.logo.as_ref()
+    }
+
+    pub fn related(&self) -> Option<&Links> {
+        self.related.as_ref()
+    }
+
+    pub fn see_also(&self) -> Option<&Links> {
+        self.see_also.as_ref()
+    }
+
+    pub fn rendering(&self) -> Option<&Links> {
+        self.rendering.as_ref()
+    }
+
+    /// 右から左に読むか否か(和書など)
+    /// sequenceの指定を優先する
+    pub fn right_to_left(&self) -> bool {
+        self.sequences.first()
+            .and_then(|sequence| sequence.viewing_direction.as_ref())
+            .or(self.viewing_direction.as_ref())
+            .map_or(false, |direction| direction == "right-to-left")
+    }
+
+    /// すべてのCanvas
+    pub fn canvas_list(&self) -> Vec<&Canvas> {
+        self.sequences.iter().flat_map(|sequence| &sequence.canvases).collect()
+    }
+
+    /// Canvasの@idとlabel
+    pub fn canvases(&self) -> Vec<(&str, &str)> {
+        self.sequences.iter()
+            .flat_map(|sequence| &sequence.canvases)
+            .map(|canvas| (canvas.id.as_str(), canvas.label.as_str()))
+            .collect()
+    }
+
+    /// IIIF Content Searchのサービス
+    pub fn search_service(&self) -> Option<&ManifestService> {
+        self.service.as_ref()?.find(|p| p.starts_with("http://iiif.io/api/search/") && p.ends_with("/search"))
+    }
+
     pub fn get_viewer_images(&self) -> Vec<ViewerImage> {
         let mut viewer_images = Vec::new();
 
         for sequence in &self.sequences {
             for canvas in &sequence.canvases {
                 let label = &canvas.label;
-                let thumbnail = match &canvas.thumbnail {
-                    Some(t) => { Some(t.id.as_str()) }
-                    None => None,
-                };
                 for image in &canvas.images {
-                    let viewer_image = ViewerImage::new(image.src(), label, thumbnail);
+                    // canvas, Image API, sequenceの順に探す
+                    // どれも無ければIconViewで画像を縮小して作る
+                    let thumbnail = canvas.thumbnail.as_ref().map(|t| t.id.clone())
+                        .or_else(|| image.thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT))
+                        .or_else(|| sequence.thumbnail.as_ref().map(|t| t.id.clone()));
+                    let mut viewer_image = ViewerImage::new(image.src(), label, thumbnail.as_ref().map(String::as_str));
+                    viewer_image.canvas = canvas.id.clone();
+                    viewer_image.canvas_width = canvas.width;
+                    viewer_image.canvas_height = canvas.height;
+                    viewer_image.service = image.service().map(str::to_string);
+                    viewer_image.service_level = image.level();
                     viewer_images.push(viewer_image);
                 }
             }
@@ -103,6 +399,8 @@ struct Sequence {
     #[serde(rename = "@type")]
     type_: String,
     thumbnail: Option<Thumbnail>,
+    #[serde(rename = "viewingDirection")]
+    viewing_direction: Option<String>,
     canvases: Vec<Canvas>,
 }
 
@@ -115,7 +413,7 @@ impl ManifestSubstructure for Sequence {
 }
 
 #[derive(Deserialize, Debug, Serialize)]
-struct Canvas {
+pub struct Canvas {
     #[serde(rename = "@id")]
     id: String,
     #[serde(rename = "@type")]
@@ -123,10 +421,32 @@ struct Canvas {
     width: u32,
     height: u32,
     label: String,
+    description: Option<Label>,
+    /// Canvasごとのlabel/valueの組
+    #[serde(default)]
+    metadata: Vec<MetadataEntry>,
     thumbnail: Option<Thumbnail>,
     images: Vec<Image>,
 }
 
+impl Canvas {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn description(&self) -> Option<&Label> {
+        self.description.as_ref()
+    }
+
+    pub fn metadata(&self) -> &Vec<MetadataEntry> {
+        &self.metadata
+    }
+}
+
 impl ManifestSubstructure for Canvas {
     fn to_image_list(&self) -> Vec<Element> {
         let mut elems = Vec::new();
@@ -167,6 +487,28 @@ impl Image {
     pub fn src(&self) -> &String {
         &self.resource.id
     }
+
+    /// Image APIのservice(@id)
+    pub fn service(&self) -> Option<&str> {
+        self.resource.service.as_ref().map(|service| service.id.as_str())
+    }
+
+    /// Image APIのcompliance level(profileの`level0.json`など)
+    pub fn level(&self) -> Option<u8> {
+        let profile = &self.resource.service.as_ref()?.profile;
+        let level = profile.rsplit("level").next()?.trim_end_matches(".json");
+        level.parse().ok()
+    }
+
+    /// Image APIで大きさを指定したthumbnailのURL
+    /// level0は任意の大きさに対応しないのでNone
+    pub fn thumbnail(&self, width: u32, height: u32) -> Option<String> {
+        let service = self.resource.service.as_ref()?;
+        if service.profile.ends_with("level0.json") {
+            return None;
+        }
+        Some(ImageRequest::new(&service.id).size(Size::BestFit(width, height)).to_string())
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -187,7 +529,9 @@ struct Resource {
     format: Option<String>,
     width: Option<u32>,
     height: Option<u32>,
-    service: Service,
+    /// Image APIのサービス
+    #[serde(default)]
+    service: Option<Service>,
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -0,0 +1,234 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_image_api_url_test() {
+        let url = "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1/full/512,/0/native.jpg";
+        let request = url.parse::<ImageRequest>().unwrap();
+        assert_eq!(request.service, "http://gallica.bnf.fr/iiif/ark:/12148/btv1b83043196/f1");
+        assert_eq!(request.region, Region::Full);
+        assert_eq!(request.size, Size::Width(512));
+        assert_eq!(request.quality, "native");
+        assert_eq!(request.format, "jpg");
+        assert_eq!(request.to_string(), url);
+
+        assert!("http://example.com/image.jpg".parse::<ImageRequest>().is_err());
+        assert!("http://example.com/a/b/full/full/x/default.jpg".parse::<ImageRequest>().is_err());
+    }
+
+    #[test]
+    fn build_image_api_url_test() {
+        let request = ImageRequest::new("https://example.com/iiif/1/")
+            .region(Region::Pixel(10, 20, 300, 400))
+            .size(Size::BestFit(200, 200))
+            .rotation(90.0, true)
+            .quality("gray");
+        assert_eq!(request.to_string(), "https://example.com/iiif/1/10,20,300,400/!200,200/!90/gray.jpg");
+
+        let request = ImageRequest::new("https://example.com/iiif/1")
+            .region(Region::Percent(12.5, 0.0, 50.0, 33.333));
+        assert_eq!(request.to_string(), "https://example.com/iiif/1/pct:12.5,0,50,33.333/full/0/default.jpg");
+    }
+}
+
+/// IIIF Image APIのregion
+#[derive(Clone, Debug, PartialEq)]
+pub enum Region {
+    Full,
+    Square,
+    /// x, y, w, h (px)
+    Pixel(u32, u32, u32, u32),
+    /// x, y, w, h (%)
+    Percent(f64, f64, f64, f64),
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Full => write!(f, "full"),
+            Region::Square => write!(f, "square"),
+            Region::Pixel(x, y, w, h) => write!(f, "{},{},{},{}", x, y, w, h),
+            Region::Percent(x, y, w, h) => write!(f, "pct:{},{},{},{}", round(*x), round(*y), round(*w), round(*h)),
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => return Ok(Region::Full),
+            "square" => return Ok(Region::Square),
+            _ => {}
+        }
+        if s.starts_with("pct:") {
+            let v = parse_numbers::<f64>(&s[4..], 4)?;
+            Ok(Region::Percent(v[0], v[1], v[2], v[3]))
+        } else {
+            let v = parse_numbers::<u32>(s, 4)?;
+            Ok(Region::Pixel(v[0], v[1], v[2], v[3]))
+        }
+    }
+}
+
+/// IIIF Image APIのsize
+#[derive(Clone, Debug, PartialEq)]
+pub enum Size {
+    Full,
+    Max,
+    /// `w,`
+    Width(u32),
+    /// `,h`
+    Height(u32),
+    /// `pct:n`
+    Percent(f64),
+    /// `w,h`
+    Exact(u32, u32),
+    /// `!w,h`
+    BestFit(u32, u32),
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Size::Full => write!(f, "full"),
+            Size::Max => write!(f, "max"),
+            Size::Width(w) => write!(f, "{},", w),
+            Size::Height(h) => write!(f, ",{}", h),
+            Size::Percent(n) => write!(f, "pct:{}", round(*n)),
+            Size::Exact(w, h) => write!(f, "{},{}", w, h),
+            Size::BestFit(w, h) => write!(f, "!{},{}", w, h),
+        }
+    }
+}
+
+impl FromStr for Size {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => return Ok(Size::Full),
+            "max" => return Ok(Size::Max),
+            _ => {}
+        }
+        if s.starts_with("pct:") {
+            return s[4..].parse().map(Size::Percent).map_err(|_| ());
+        }
+        let (best_fit, s) = if s.starts_with('!') { (true, &s[1..]) } else { (false, s) };
+        let mut split = s.splitn(2, ',');
+        let w = split.next().ok_or(())?;
+        let h = split.next().ok_or(())?;
+        match (w.parse::<u32>().ok(), h.parse::<u32>().ok(), best_fit) {
+            (Some(w), Some(h), true) => Ok(Size::BestFit(w, h)),
+            (Some(w), Some(h), false) => Ok(Size::Exact(w, h)),
+            (Some(w), None, false) if h.is_empty() => Ok(Size::Width(w)),
+            (None, Some(h), false) if w.is_empty() => Ok(Size::Height(h)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// IIIF Image APIの画像リクエスト
+/// `{service}/{region}/{size}/{rotation}/{quality}.{format}`
+/// [参考](https://iiif.io/api/image/2.1/#image-request-parameters)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageRequest {
+    pub service: String,
+    pub region: Region,
+    pub size: Size,
+    /// 時計回りの角度
+    pub rotation: f64,
+    /// 左右反転するか否か
+    pub mirror: bool,
+    pub quality: String,
+    pub format: String,
+}
+
+impl ImageRequest {
+    /// serviceの@idから全体画像のリクエストを作る
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.trim_end_matches('/').to_string(),
+            region: Region::Full,
+            size: Size::Full,
+            rotation: 0.0,
+            mirror: false,
+            quality: "default".to_string(),
+            format: "jpg".to_string(),
+        }
+    }
+
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = region;
+        self
+    }
+
+    pub fn size(mut self, size: Size) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f64, mirror: bool) -> Self {
+        self.rotation = rotation;
+        self.mirror = mirror;
+        self
+    }
+
+    pub fn quality(mut self, quality: &str) -> Self {
+        self.quality = quality.to_string();
+        self
+    }
+}
+
+impl fmt::Display for ImageRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{}/{}{}/{}.{}",
+               self.service, self.region, self.size,
+               if self.mirror { "!" } else { "" }, round(self.rotation),
+               self.quality, self.format)
+    }
+}
+
+impl FromStr for ImageRequest {
+    type Err = ();
+
+    /// Image APIの画像URLを分解する
+    /// Image APIの形式でなければErr
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = s.rsplitn(5, '/');
+        let file = segments.next().ok_or(())?;
+        let rotation = segments.next().ok_or(())?;
+        let size = segments.next().ok_or(())?.parse()?;
+        let region = segments.next().ok_or(())?.parse()?;
+        let service = segments.next().ok_or(())?;
+        if !service.contains("://") {
+            return Err(());
+        }
+
+        let (mirror, rotation) = if rotation.starts_with('!') { (true, &rotation[1..]) } else { (false, rotation) };
+        let rotation = rotation.parse().map_err(|_| ())?;
+        let mut file = file.splitn(2, '.');
+        let quality = file.next().ok_or(())?.to_string();
+        let format = file.next().ok_or(())?.to_string();
+
+        Ok(Self { service: service.to_string(), region, size, rotation, mirror, quality, format })
+    }
+}
+
+/// `,`区切りの数をlen個読む
+fn parse_numbers<T: FromStr>(s: &str, len: usize) -> Result<Vec<T>, ()> {
+    let numbers = s.split(',')
+        .map(|n| n.parse::<T>().map_err(|_| ()))
+        .collect::<Result<Vec<T>, ()>>()?;
+    if numbers.len() == len { Ok(numbers) } else { Err(()) }
+}
+
+/// URLに載せる数を小数点以下3桁までにする
+fn round(n: f64) -> f64 {
+    (n * 1000.0).round() / 1000.0
+}
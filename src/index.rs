@@ -0,0 +1,279 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::*;
+
+use crate::iiif_manifest::Manifest;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokenize_test() {
+        assert_eq!(tokenize("Kyôka surimono / Ill. HOKUSAI", false), vec!["kyoka", "surimono", "ill", "hokusai"]);
+        assert_eq!(tokenize("葛飾北斎画", false), vec!["葛飾", "飾北", "北斎", "斎画"]);
+        assert_eq!(tokenize("北斎", true), vec!["北", "斎", "北斎"]);
+        assert_eq!(tokenize("狂歌摺物 Surimono 摺", false), vec!["狂歌", "歌摺", "摺物", "surimono", "摺"]);
+    }
+
+    #[test]
+    fn search_index_test() {
+        let mut index = Index::new();
+        let manifest = serde_json::from_str::<Manifest>(include_str!("../test/hokusai.json")).unwrap();
+        index.add(&manifest);
+        let manifest2 = serde_json::from_str::<Manifest>(include_str!("../test/hokusai2.json")).unwrap();
+        index.add(&manifest2);
+
+        let hits = index.search("北斎", 10);
+        assert!(!hits.is_empty());
+        assert_eq!(hits[0].canvas, None);
+
+        let hits = index.search("surimono", 10);
+        assert!(hits.iter().any(|hit| hit.manifest == manifest.id()));
+
+        // すべての語を含むものだけ
+        assert!(index.search("surimono zzzz", 10).is_empty());
+        assert!(index.search("", 10).is_empty());
+
+        // 同じManifestを加え直しても重複しない
+        let before = index.search("surimono", 100).len();
+        index.add(&manifest);
+        assert_eq!(index.search("surimono", 100).len(), before);
+
+        index.remove(manifest.id());
+        assert!(index.search("surimono", 100).iter().all(|hit| hit.manifest != manifest.id()));
+    }
+}
+
+thread_local! {
+    /// 読み込んだManifestの索引
+    static INDEX: RefCell<Index> = RefCell::new(Index::new());
+}
+
+/// 読み込んだManifestを索引に加える
+pub fn add_manifest(manifest: &Manifest) {
+    INDEX.with(|index| index.borrow_mut().add(manifest));
+}
+
+/// 保存しておいたManifest(JSON)を索引に加える
+#[wasm_bindgen]
+pub fn index_manifest(json: String) -> bool {
+    match serde_json::from_str::<Manifest>(&json) {
+        Ok(manifest) => {
+            add_manifest(&manifest);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Manifestを索引から除く
+#[wasm_bindgen]
+pub fn unindex_manifest(id: String) {
+    INDEX.with(|index| index.borrow_mut().remove(&id));
+}
+
+/// 索引を検索する(JSON配列, 関連度の高い順)
+/// `[{"manifest": "...", "label": "...", "canvas": "...", "canvas_label": "...", "score": 1.2}, ...]`
+#[wasm_bindgen]
+pub fn search_index(q: String, limit: usize) -> String {
+    let hits = INDEX.with(|index| index.borrow().search(&q, limit));
+    serde_json::to_string(&hits).unwrap_or(String::new())
+}
+
+/// 項目ごとの重み
+const LABEL_WEIGHT: f64 = 3.0;
+const CANVAS_LABEL_WEIGHT: f64 = 2.0;
+const TEXT_WEIGHT: f64 = 1.0;
+
+/// 索引に載せる単位(ManifestまたはCanvas)
+struct Document {
+    manifest: String,
+    label: String,
+    canvas: Option<String>,
+    canvas_label: Option<String>,
+}
+
+/// 検索結果(1件)
+#[derive(Serialize, Debug)]
+pub struct IndexHit {
+    pub manifest: String,
+    pub label: String,
+    pub canvas: Option<String>,
+    pub canvas_label: Option<String>,
+    pub score: f64,
+}
+
+/// 転置索引
+pub struct Index {
+    /// 除いた文書はNone
+    documents: Vec<Option<Document>>,
+    /// 語 -> 文書 -> 重み付きの出現回数
+    postings: HashMap<String, HashMap<usize, f64>>,
+}
+
+impl Index {
+    pub fn new() -> Self {
+        Self { documents: Vec::new(), postings: HashMap::new() }
+    }
+
+    /// Manifestと各Canvasを索引に加える
+    /// 既にあれば加え直す
+    pub fn add(&mut self, manifest: &Manifest) {
+        self.remove(manifest.id());
+
+        let label = manifest.label.texts().join(" ");
+        let mut texts = vec![(label.clone(), LABEL_WEIGHT)];
+        if let Some(description) = manifest.description() {
+            texts.extend(description.texts().into_iter().map(|t| (t.to_string(), TEXT_WEIGHT)));
+        }
+        for entry in manifest.metadata() {
+            texts.extend(entry.value.texts().into_iter().map(|t| (t.to_string(), TEXT_WEIGHT)));
+        }
+        let document = Document { manifest: manifest.id().to_string(), label: label.clone(), canvas: None, canvas_label: None };
+        self.add_document(document, &texts);
+
+        for (canvas, canvas_label) in manifest.canvases() {
+            let document = Document {
+                manifest: manifest.id().to_string(),
+                label: label.clone(),
+                canvas: Some(canvas.to_string()),
+                canvas_label: Some(canvas_label.to_string()),
+            };
+            self.add_document(document, &vec![(canvas_label.to_string(), CANVAS_LABEL_WEIGHT)]);
+        }
+    }
+
+    fn add_document(&mut self, document: Document, texts: &Vec<(String, f64)>) {
+        let id = self.documents.len();
+        self.documents.push(Some(document));
+        for (text, weight) in texts {
+            for token in tokenize(text, true) {
+                *self.postings.entry(token).or_insert_with(HashMap::new).entry(id).or_insert(0.0) += weight;
+            }
+        }
+    }
+
+    /// Manifestとその各Canvasを索引から除く
+    pub fn remove(&mut self, manifest: &str) {
+        let removed: HashSet<usize> = self.documents.iter().enumerate()
+            .filter(|(_, d)| d.as_ref().map(|d| d.manifest == manifest).unwrap_or(false))
+            .map(|(i, _)| i)
+            .collect();
+        if removed.is_empty() {
+            return;
+        }
+        for &i in &removed {
+            self.documents[i] = None;
+        }
+        for postings in self.postings.values_mut() {
+            postings.retain(|i, _| !removed.contains(i));
+        }
+        self.postings.retain(|_, postings| !postings.is_empty());
+    }
+
+    /// すべての語を含む文書を関連度(tf-idf)の高い順に返す
+    pub fn search(&self, q: &str, limit: usize) -> Vec<IndexHit> {
+        let mut terms = tokenize(q, false);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let count = self.documents.iter().filter(|d| d.is_some()).count() as f64;
+        let mut scores: Option<HashMap<usize, f64>> = None;
+        for term in &terms {
+            let postings = match self.postings.get(term) {
+                Some(postings) => postings,
+                None => return Vec::new(),
+            };
+            let idf = (1.0 + count / postings.len() as f64).ln();
+            scores = Some(match scores {
+                None => postings.iter().map(|(&i, tf)| (i, tf * idf)).collect(),
+                Some(scores) => scores.into_iter()
+                    .filter_map(|(i, score)| postings.get(&i).map(|tf| (i, score + tf * idf)))
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<(usize, f64)> = scores.unwrap_or_default().into_iter().collect();
+        hits.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        hits.into_iter()
+            .take(limit)
+            .filter_map(|(i, score)| {
+                let document = self.documents[i].as_ref()?;
+                Some(IndexHit {
+                    manifest: document.manifest.clone(),
+                    label: document.label.clone(),
+                    canvas: document.canvas.clone(),
+                    canvas_label: document.canvas_label.clone(),
+                    score,
+                })
+            })
+            .collect()
+    }
+}
+
+/// 文字列を語に分ける
+/// 英数字は単語ごと、日本語などは2文字ずつ(bigram)に分ける
+/// unigramsなら日本語などの1文字ずつも加える(1文字の検索語のため)
+pub fn tokenize(text: &str, unigrams: bool) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk: Vec<char> = Vec::new();
+
+    for c in text.chars().flat_map(char::to_lowercase).map(fold) {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut tokens, unigrams);
+            word.push(c);
+        } else {
+            flush_word(&mut word, &mut tokens);
+            flush_cjk(&mut cjk, &mut tokens, unigrams);
+        }
+    }
+    flush_word(&mut word, &mut tokens);
+    flush_cjk(&mut cjk, &mut tokens, unigrams);
+    tokens
+}
+
+fn flush_word(word: &mut String, tokens: &mut Vec<String>) {
+    if !word.is_empty() {
+        tokens.push(std::mem::replace(word, String::new()));
+    }
+}
+
+fn flush_cjk(cjk: &mut Vec<char>, tokens: &mut Vec<String>, unigrams: bool) {
+    if cjk.len() == 1 || unigrams {
+        tokens.extend(cjk.iter().map(|c| c.to_string()));
+    }
+    tokens.extend(cjk.windows(2).map(|w| w.iter().collect::<String>()));
+    cjk.clear();
+}
+
+/// 漢字・かな・ハングルか否か
+fn is_cjk(c: char) -> bool {
+    match c as u32 {
+        0x3005 | 0x3040..=0x30FF | 0x31F0..=0x31FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF66..=0xFF9F => true,
+        _ => false,
+    }
+}
+
+/// アクセント記号・長音記号を外す(Kyôka, Hokusaï, Tōkyō など)
+fn fold(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => 'a',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' => 'u',
+        'ç' => 'c',
+        'ñ' => 'n',
+        _ => c,
+    }
+}
@@ -3,6 +3,13 @@ extern crate serde_derive;
 
 mod viewer;
 mod iiif_manifest;
+mod image_api;
 mod view;
 mod search;
-mod curation;
\ No newline at end of file
+mod content_search;
+mod index;
+mod curation;
+mod storage;
+mod sync;
+mod compare;
+mod adjustment;
\ No newline at end of file
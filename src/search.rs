@@ -1,5 +1,271 @@
 use wasm_bindgen::prelude::*;
 use std::str::FromStr;
+use std::fmt;
+
+use crate::storage::{LocalStorage, SearchLibrary, StorageError};
+use crate::viewer::log;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_europeana_search_test() {
+        let results = include_str!("../test/europeana_search.json").parse::<SearchResults>();
+        match &results {
+            Err(e) => println!("{}", &e),
+            _ => {}
+        }
+        let results = results.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results.total(), 1342);
+        assert_eq!(results.next_cursor().unwrap(), "AoE/GS85MjAwNTE4L2Fya19fMTIxNDhfYnR2MWI4MzA0MzE5Ng==");
+
+        let result = results.get(0).unwrap();
+        assert_eq!(result.title(), "Kyôka surimono / Ill. Katsushika Hokusai");
+        assert_eq!(result.description(), "Appartient à l'ensemble documentaire : FranceJp0");
+        assert!(result.thumbnail().unwrap().starts_with("https://api.europeana.eu/thumbnail/v2/"));
+        assert_eq!(result.manifest().unwrap(), "https://iiif.europeana.eu/presentation/9200518/ark__12148_btv1b83043196/manifest");
+
+        let result = results.get(1).unwrap();
+        assert_eq!(result.title(), "The Great Wave off Kanagawa");
+        assert_eq!(result.description(), "");
+
+        let result = results.get(2).unwrap();
+        assert_eq!(result.description(), "Holzschnitt nach Hokusai");
+        assert_eq!(result.thumbnail(), None);
+        assert_eq!(result.manifest(), None);
+    }
+
+    #[test]
+    fn parse_europeana_search_error_test() {
+        let error = r#"{"apikey":"xxx","success":false,"error":"Invalid API key"}"#.parse::<SearchResults>();
+        match error {
+            Err(SearchError::Api(message)) => assert_eq!(message, "Invalid API key"),
+            _ => panic!(),
+        }
+        assert!("[]".parse::<SearchResults>().is_err());
+    }
+
+    #[test]
+    fn next_page_test() {
+        let results = include_str!("../test/europeana_search.json").parse::<SearchResults>().unwrap();
+
+        let mut query = SearchQuery::new("hokusai".to_string());
+        query.set_rows(3);
+        let next = query.next_page(&results).unwrap();
+        assert_eq!(next.start(), 4);
+        assert_eq!(next.cursor(), None);
+
+        query.set_start(1341);
+        assert!(query.next_page(&results).is_none());
+
+        query.set_cursor(Some("*".to_string()));
+        let next = query.next_page(&results).unwrap();
+        assert_eq!(next.cursor(), results.next_cursor());
+        assert_eq!(next.query(), "hokusai");
+
+        let last = r#"{"success":true,"items":[],"totalResults":1342}"#.parse::<SearchResults>().unwrap();
+        assert!(next.next_page(&last).is_none());
+    }
+
+    #[test]
+    fn parse_facets_test() {
+        let results = include_str!("../test/europeana_search_facets.json").parse::<SearchResults>().unwrap();
+        assert_eq!(results.facets.len(), 3);
+        assert_eq!(results.facets[1].name, "COUNTRY");
+        assert_eq!(results.facets[1].fields[2].label, "united kingdom");
+        assert_eq!(results.facets[1].fields[2].count, 5);
+    }
+
+    #[test]
+    fn refinement_params_test() {
+        let mut query = SearchQuery::new("hokusai wave".to_string());
+        query.add_type("IMAGE".to_string());
+        query.refine("COUNTRY".to_string(), "united kingdom".to_string());
+        query.set_year_range(1800, 1850);
+        query.set_reusability(Some("open".to_string()));
+        query.set_has_iiif(true);
+        query.add_facet("TYPE".to_string());
+        query.add_facet("COUNTRY".to_string());
+
+        assert_eq!(query.europeana_url("api2demo".to_string()),
+                   "https://api.europeana.eu/record/v2/search.json?wskey=api2demo&query=hokusai%20wave&rows=10&start=1\
+                   &qf=TYPE%3AIMAGE&qf=COUNTRY%3A%22united%20kingdom%22&qf=YEAR%3A%5B1800%20TO%201850%5D\
+                   &reusability=open&qf=sv_dcterms_conformsTo%3A%2Aiiif%2A&profile=facets&facet=TYPE%2CCOUNTRY");
+
+        // 同じ種類の範囲指定は置き換える
+        query.set_year_range(1830, 1831);
+        query.set_has_iiif(false);
+        assert_eq!(query.filters.len(), 4);
+        assert_eq!(query.filters[3], Refinement::Year(1830, 1831));
+    }
+
+    #[test]
+    fn query_syntax_test() {
+        let query = SearchQuery::parse(r#"hokusai type:image year:1800-1850 provider:"BnF" -country:france "great wave" -fuji"#.to_string()).ok().unwrap();
+        assert_eq!(query.query(), r#"hokusai "great wave" -fuji"#);
+        assert_eq!(query.filters, vec![
+            Refinement::Type("IMAGE".to_string()),
+            Refinement::Year(1800, 1850),
+            Refinement::Provider("BnF".to_string()),
+            Refinement::Not(Box::new(Refinement::Country("france".to_string()))),
+        ]);
+        assert!(query.europeana_url("api2demo".to_string()).ends_with("&qf=-COUNTRY%3Afrance"));
+
+        // 逆変換して読み直しても同じ
+        let text = query.to_query_string();
+        assert_eq!(text, r#"hokusai "great wave" -fuji type:IMAGE year:1800-1850 provider:BnF -country:france"#);
+        let again = syntax::parse(&text).unwrap();
+        assert!(again.same_search(&query));
+
+        let query = syntax::parse("has:iiif reuse:open media:yes iiif:false who:\"Katsushika Hokusai\"").unwrap();
+        assert_eq!(query.query(), "");
+        assert_eq!(query.to_query_string(), r#"has:iiif reuse:open media:true who:"Katsushika Hokusai""#);
+
+        // 知らない項目は絞り込みにせず検索語にする
+        let query = syntax::parse("http://example.org/item/1 -ark:/12148/btv1b83043196 LANGUAGE:ja").unwrap();
+        assert_eq!(query.query(), r#""http://example.org/item/1" -"ark:/12148/btv1b83043196""#);
+        assert_eq!(query.filters, vec![Refinement::Field("LANGUAGE".to_string(), "ja".to_string())]);
+    }
+
+    #[test]
+    fn query_syntax_error_test() {
+        use syntax::SyntaxError;
+        assert_eq!(syntax::parse(r#"hokusai "great wave"#).err(), Some(SyntaxError::UnclosedQuote(9)));
+        assert_eq!(syntax::parse("type: image").err(), Some(SyntaxError::EmptyValue("type".to_string())));
+        assert_eq!(syntax::parse("year:18xx").err(), Some(SyntaxError::InvalidYear("18xx".to_string())));
+        assert!(syntax::parse("reuse:free").is_err());
+        assert_eq!(syntax::parse("-reuse:open").err(), Some(SyntaxError::CannotNegate("reuse".to_string())));
+        assert_eq!(syntax::parse("year:1850-1800").unwrap().filters, vec![Refinement::Year(1800, 1850)]);
+    }
+
+    #[test]
+    fn japan_search_test() {
+        let provider = japan_search::JapanSearch::new();
+        let mut query = SearchQuery::new("北斎".to_string());
+        query.set_rows(20);
+        query.set_start(21);
+        query.set_year_range(1800, 1850);
+        query.set_has_iiif(true);
+        query.add_country("japan".to_string());
+        assert_eq!(provider.url(&query).unwrap(),
+                   "https://jpsearch.go.jp/api/item/search/jps-cross?keyword=%E5%8C%97%E6%96%8E&size=20&from=20\
+                   &r-tempo=1800-1850&f-contents=iiif_manifest");
+        // 否定とEuropeanaの項目は扱えない
+        let mut field = query.clone();
+        field.add_filter(Refinement::Field("LANGUAGE".to_string(), "ja".to_string()));
+        assert_eq!(provider.url(&field).err().unwrap().to_string(), "Japan Search does not support LANGUAGE:ja");
+        let mut field = query.clone();
+        field.add_filter(Refinement::Field("who".to_string(), "Hokusai".to_string()));
+        assert_eq!(provider.url(&field).err().unwrap().to_string(), "Japan Search does not support who:Hokusai");
+        query.add_filter(Refinement::Not(Box::new(Refinement::Type("IMAGE".to_string()))));
+        assert_eq!(provider.url(&query).err().unwrap().to_string(), "Japan Search does not support -type:IMAGE");
+
+        let results = provider.parse(include_str!("../test/japan_search.json")).unwrap();
+        assert_eq!(results.total(), 214);
+        assert_eq!(results.len(), 3);
+        let result = results.get(0).unwrap();
+        assert_eq!(result.title(), "富嶽三十六景 神奈川沖浪裏");
+        assert_eq!(result.description(), "葛飾北斎 / 錦絵 / 横大判");
+        assert_eq!(result.url(), "https://dl.ndl.go.jp/pid/1307775");
+        assert_eq!(result.manifest().unwrap(), "https://dl.ndl.go.jp/api/iiif/1307775/manifest.json");
+        assert_eq!(results.get(2).unwrap().thumbnail(), None);
+
+        assert!(provider.parse("{").is_err());
+    }
+
+    #[test]
+    fn federated_search_test() {
+        let mut search = FederatedSearch::new();
+        search.add(WasmSearchProvider { provider: Box::new(europeana::Europeana::new("api2demo".to_string())) });
+        search.add(WasmSearchProvider { provider: Box::new(japan_search::JapanSearch::new()) });
+        search.add(WasmSearchProvider { provider: Box::new(japan_search::JapanSearch::new()) });
+        let query = SearchQuery::new("hokusai".to_string());
+
+        let urls: Vec<Option<String>> = serde_json::from_str(&search.start(&query)).unwrap();
+        assert_eq!(urls.len(), 3);
+        assert!(urls[1].as_ref().unwrap().starts_with("https://jpsearch.go.jp/"));
+        assert!(!search.is_complete());
+        assert!(search.receive(0, include_str!("../test/europeana_search.json").to_string()));
+        assert!(search.receive(1, include_str!("../test/japan_search.json").to_string()));
+        assert!(!search.receive(2, "<html>".to_string()));
+        assert!(search.is_complete());
+
+        let results = search.results();
+        assert_eq!(results.total(), 1342 + 214);
+        let titles: Vec<String> = (0..results.len()).map(|i| results.get(i).unwrap().title()).collect();
+        assert_eq!(titles[0], "Kyôka surimono / Ill. Katsushika Hokusai");
+        assert_eq!(titles[1], "富嶽三十六景 神奈川沖浪裏");
+        assert_eq!(results.get(1).unwrap().provider().unwrap(), "Japan Search");
+        assert_eq!(results.len(), 6);
+
+        let failures: serde_json::Value = serde_json::from_str(&search.failures()).unwrap();
+        assert_eq!(failures.as_array().unwrap().len(), 1);
+        assert_eq!(failures[0]["provider"], "Japan Search");
+
+        // 重複は除き、件数からも引く
+        search.receive(2, include_str!("../test/japan_search.json").to_string());
+        assert_eq!(search.results().len(), 6);
+        assert_eq!(search.results().total(), 1342 + 214 * 2 - 3);
+
+        search.set_ranking(Ranking::Sequential);
+        assert_eq!(search.results().get(2).unwrap().provider().unwrap(), "Europeana");
+        search.set_ranking(Ranking::Interleave);
+        search.set_weight(1, 3.0);
+        assert_eq!(search.results().get(1).unwrap().provider().unwrap(), "Japan Search");
+        assert_eq!(search.results().get(2).unwrap().provider().unwrap(), "Japan Search");
+
+        // 新しい検索では前回の結果を残さない
+        search.start(&SearchQuery::new("hiroshige".to_string()));
+        assert!(!search.is_complete());
+        assert_eq!(search.results().len(), 0);
+        assert_eq!(search.failures(), "[]");
+
+        // 扱えない条件のサービスは問い合わせずに失敗にする
+        let query = SearchQuery::parse("hiroshige -type:image".to_string()).ok().unwrap();
+        let urls: Vec<Option<String>> = serde_json::from_str(&search.start(&query)).unwrap();
+        assert!(urls[0].is_some());
+        assert_eq!(urls[1], None);
+        let failures: serde_json::Value = serde_json::from_str(&search.failures()).unwrap();
+        assert_eq!(failures.as_array().unwrap().len(), 2);
+        assert_eq!(failures[0]["message"], "Japan Search does not support -type:IMAGE");
+    }
+
+    #[test]
+    fn resolve_manifest_test() {
+        let result = SearchResult::new("https://example.org/viewer?lang=ja&manifest=https%3A%2F%2Fexample.org%2Fiiif%2F1%2Fmanifest.json".to_string(),
+                                       "Hokusai".to_string(), String::new(), None);
+        assert_eq!(result.resolve_manifest().unwrap(), "https://example.org/iiif/1/manifest.json");
+        let result = SearchResult::new("https://example.org/item/1".to_string(), "Hokusai".to_string(), String::new(), None);
+        assert_eq!(result.resolve_manifest(), None);
+        assert_eq!(result.no_manifest().to_string(), "\"Hokusai\" has no IIIF manifest");
+
+        let html = r#"<html><head>
+            <LINK rel="stylesheet" href="/css/main.css">
+            <link rel="alternate" type="application/ld+json" profile="http://iiif.io/api/presentation/2/context.json" href="/iiif/1/manifest">
+            </head></html>"#;
+        assert_eq!(discovery::discover_manifest(html, "https://example.org/item/1").unwrap(), "https://example.org/iiif/1/manifest");
+
+        let html = r#"<body><a href='https://example.org/'>top</a>
+            <a class="iiif" href="https://viewer.example.com/?manifest=https://example.org/iiif/2/manifest.json&amp;page=1"><img src="iiif.png"></a></body>"#;
+        assert_eq!(discovery::discover_manifest(html, "https://example.org/item/2").unwrap(), "https://example.org/iiif/2/manifest.json");
+        assert_eq!(discovery::discover_manifest("<p>no manifest</p>", "https://example.org/item/3"), None);
+
+        // 小文字にすると長さの変わる文字があっても位置がずれない
+        let html = "İ<a href=\"https://viewer.example.com/?manifest=https://example.org/iiif/4/manifest.json\">";
+        assert_eq!(discovery::discover_manifest(html, "https://example.org/item/4").unwrap(), "https://example.org/iiif/4/manifest.json");
+        assert_eq!(discovery::discover_manifest("\u{130}\u{130}<a   href=\"x\">\u{3042}</a>", "https://example.org/item/5"), None);
+    }
+
+    #[test]
+    fn europeana_provider_test() {
+        let provider = europeana::Europeana::new("api2demo".to_string());
+        let query = SearchQuery::new("hokusai".to_string());
+        assert_eq!(provider.url(&query).unwrap(), query.europeana_url("api2demo".to_string()));
+        assert_eq!(provider.parse(include_str!("../test/europeana_search.json")).unwrap().len(), 3);
+    }
+}
 
 /// サーバーから送られてくる検索結果(1件)
 #[wasm_bindgen]
@@ -9,13 +275,19 @@ pub struct SearchResult {
     title: String,
     description: String,
     thumbnail: Option<String>,
+    /// IIIF ManifestのURL
+    #[serde(default)]
+    manifest: Option<String>,
+    /// 検索したサービスの名前
+    #[serde(default)]
+    provider: Option<String>,
 }
 
 #[wasm_bindgen]
 impl SearchResult {
     #[wasm_bindgen(constructor)]
     pub fn new(url: String, title: String, description: String, thumbnail: Option<String>) -> Self {
-        Self { url, title, description, thumbnail }
+        Self { url, title, description, thumbnail, manifest: None, provider: None }
     }
 
     pub fn url(&self) -> String {
@@ -33,52 +305,916 @@ impl SearchResult {
     pub fn thumbnail(&self) -> Option<String> {
         self.thumbnail.clone()
     }
+
+    pub fn manifest(&self) -> Option<String> {
+        self.manifest.clone()
+    }
+
+    pub fn provider(&self) -> Option<String> {
+        self.provider.clone()
+    }
+
+    /// IIIF ManifestのURL
+    /// 検索結果の項目に無ければURLから推測する(`?manifest=...` など)
+    pub fn resolve_manifest(&self) -> Option<String> {
+        self.manifest.clone().or_else(|| discovery::manifest_from_url(&self.url))
+    }
+}
+
+impl SearchResult {
+    /// Manifestが無いときのエラー
+    pub fn no_manifest(&self) -> SearchError {
+        SearchError::NoManifest(self.title.clone())
+    }
+}
+
+/// 検索結果のページからIIIF Manifestを探す
+pub mod discovery {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// URLそのもの、またはURLの `manifest` パラメータ
+    pub fn manifest_from_url(url: &str) -> Option<String> {
+        let (path, query) = match url.find('?') {
+            Some(i) => (&url[..i], &url[i + 1..]),
+            None => (url, ""),
+        };
+        for param in query.split(|c| c == '&' || c == '#') {
+            let mut kv = param.splitn(2, '=');
+            if kv.next() == Some("manifest") {
+                let manifest = decode(kv.next().unwrap_or(""));
+                if manifest.starts_with("http") {
+                    return Some(manifest);
+                }
+            }
+        }
+        if path.ends_with("/manifest") || path.ends_with("manifest.json") {
+            return Some(url.to_string());
+        }
+        None
+    }
+
+    /// ページ(HTML)からManifestのURLを探す
+    /// - `<link rel="alternate" type="application/ld+json" href="...">`(IIIFのprofileまたはmanifestを含むもの)
+    /// - `<link rel="seeAlso" href="...">`
+    /// - `?manifest=...` へのリンク(IIIFアイコンなど)
+    pub fn discover_manifest(html: &str, base: &str) -> Option<String> {
+        for attrs in tags(html, "link") {
+            let rel = attrs.get("rel").map(|r| r.to_lowercase()).unwrap_or_default();
+            let href = match attrs.get("href") {
+                Some(href) => href,
+                None => continue,
+            };
+            let profile = attrs.get("profile").map(String::as_str).unwrap_or("");
+            let json = attrs.get("type").map(|t| t.contains("json")).unwrap_or(false);
+            let iiif = profile.contains("iiif.io/api/presentation") || href.contains("manifest");
+            if (rel.split_whitespace().any(|r| r == "alternate" || r == "seealso")) && json && iiif {
+                return resolve(base, href);
+            }
+        }
+        tags(html, "a").iter()
+            .filter_map(|attrs| attrs.get("href"))
+            .filter_map(|href| manifest_from_url(&resolve(base, href)?))
+            .next()
+    }
+
+    /// タグの属性を取り出す
+    /// 位置をそのまま元のHTMLに使うので、長さの変わらないASCIIだけを小文字にする
+    fn tags(html: &str, name: &str) -> Vec<HashMap<String, String>> {
+        let lower = html.to_ascii_lowercase();
+        let open = format!("<{}", name);
+        let mut tags = Vec::new();
+        let mut rest = 0;
+        while let Some(i) = lower[rest..].find(&open) {
+            let start = rest + i + open.len();
+            let end = match lower[start..].find('>') {
+                Some(end) => start + end,
+                None => break,
+            };
+            rest = end;
+            // <linkfoo> などは除く
+            if !html[start..].starts_with(char::is_whitespace) {
+                continue;
+            }
+            tags.push(attributes(&html[start..end]));
+        }
+        tags
+    }
+
+    /// `name="value"` の組
+    fn attributes(s: &str) -> HashMap<String, String> {
+        let mut attrs = HashMap::new();
+        let mut rest = s.trim_start();
+        while let Some(eq) = rest.find('=') {
+            let name = rest[..eq].trim().rsplit(char::is_whitespace).next().unwrap_or("").to_lowercase();
+            let value = rest[eq + 1..].trim_start();
+            let (value, next) = match value.chars().next() {
+                Some(q) if q == '"' || q == '\'' => match value[1..].find(q) {
+                    Some(end) => (&value[1..end + 1], &value[end + 2..]),
+                    None => (&value[1..], ""),
+                },
+                _ => {
+                    let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                    (&value[..end], &value[end..])
+                }
+            };
+            attrs.insert(name, value.replace("&amp;", "&"));
+            rest = next;
+        }
+        attrs
+    }
+
+    /// 相対URLを絶対URLにする
+    fn resolve(base: &str, href: &str) -> Option<String> {
+        if href.starts_with("http://") || href.starts_with("https://") {
+            return Some(href.to_string());
+        }
+        let scheme = base.find("://")?;
+        if href.starts_with("//") {
+            return Some(format!("{}:{}", &base[..scheme], href));
+        }
+        let origin = match base[scheme + 3..].find('/') {
+            Some(i) => &base[..scheme + 3 + i],
+            None => base,
+        };
+        if href.starts_with('/') {
+            Some(format!("{}{}", origin, href))
+        } else {
+            let dir = base.rfind('/').filter(|&i| i > scheme + 2).map(|i| &base[..i]).unwrap_or(origin);
+            Some(format!("{}/{}", dir, href))
+        }
+    }
+}
+
+/// 検索結果を読み込めなかった理由
+#[derive(Debug)]
+pub enum SearchError {
+    Json(serde_json::Error),
+    /// APIがエラーを返した
+    Api(String),
+    /// IIIF Manifestが無い(検索結果のタイトル)
+    NoManifest(String),
+    /// サービスが扱えない条件(サービスの名前, 条件)
+    Unsupported(String, String),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::Json(e) => write!(f, "Cannot read search results: {}", e),
+            SearchError::Api(message) => write!(f, "Search failed: {}", message),
+            SearchError::NoManifest(title) => write!(f, "\"{}\" has no IIIF manifest", title),
+            SearchError::Unsupported(provider, refinement) => write!(f, "{} does not support {}", provider, refinement),
+        }
+    }
+}
+
+impl From<serde_json::Error> for SearchError {
+    fn from(e: serde_json::Error) -> Self {
+        SearchError::Json(e)
+    }
 }
 
 /// サーバーから送られてくる検索結果
 #[wasm_bindgen]
 pub struct SearchResults {
-    results: Vec<SearchResult>
+    results: Vec<SearchResult>,
+    /// 検索に当てはまった件数
+    total: u32,
+    /// 次のページのcursor
+    next_cursor: Option<String>,
+    /// 項目ごとの件数
+    facets: Vec<Facet>,
+}
+
+/// 検索結果の項目ごとの件数
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Facet {
+    name: String,
+    fields: Vec<FacetField>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FacetField {
+    label: String,
+    count: u32,
 }
 
 #[wasm_bindgen]
 impl SearchResults {
     #[wasm_bindgen(constructor)]
-    pub fn new(s: String) -> SearchResults {
-        match serde_json::from_str(&s) {
-            Ok(results) => SearchResults { results },
-            Err(_) => SearchResults { results: vec![] },
-        }
+    /// Europeana Search APIのレスポンスを読み込む
+    /// 読み込めなければエラーを投げる
+    pub fn new(s: String) -> Result<SearchResults, JsValue> {
+        s.parse().map_err(|e: SearchError| JsValue::from_str(&e.to_string()))
     }
 
     pub fn len(&self) -> usize {
-        let SearchResults { results: vec } = &self;
+        let SearchResults { results: vec, .. } = &self;
         vec.len()
     }
 
     pub fn get(&self, i: usize) -> Option<SearchResult> {
-        let SearchResults { results: vec } = &self;
+        let SearchResults { results: vec, .. } = &self;
         vec.get(i).cloned()
     }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn next_cursor(&self) -> Option<String> {
+        self.next_cursor.clone()
+    }
+
+    /// 項目ごとの件数(JSON配列)
+    /// `[{"name": "TYPE", "fields": [{"label": "IMAGE", "count": 51}, ...]}, ...]`
+    pub fn facets(&self) -> String {
+        serde_json::to_string(&self.facets).unwrap_or(String::new())
+    }
+
+    /// 次のページの検索結果を後ろに追加する
+    pub fn append(&mut self, next: SearchResults) {
+        let SearchResults { mut results, total, next_cursor, facets } = next;
+        self.results.append(&mut results);
+        self.total = total;
+        self.next_cursor = next_cursor;
+        self.facets = facets;
+    }
+}
+
+impl FromStr for SearchResults {
+    type Err = SearchError;
+
+    /// Europeana Search APIのレスポンスとして読み込む
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        europeana::parse(s)
+    }
+}
+
+/// 検索サービス
+/// 共通のSearchQueryをサービスごとのURLにし、レスポンスをSearchResultsにする
+pub trait SearchProvider {
+    /// サービスの名前
+    fn name(&self) -> &str;
+    /// 検索クエリからAPIのURLを作る
+    /// 扱えない条件があればエラー
+    fn url(&self, query: &SearchQuery) -> Result<String, SearchError>;
+    /// APIのレスポンスを読み込む
+    fn parse(&self, response: &str) -> Result<SearchResults, SearchError>;
+}
+
+/// JSから使う検索サービス
+#[wasm_bindgen]
+pub struct WasmSearchProvider {
+    provider: Box<dyn SearchProvider>,
+}
+
+#[wasm_bindgen]
+impl WasmSearchProvider {
+    /// Europeana Search API
+    pub fn europeana(wskey: String) -> Self {
+        Self { provider: Box::new(europeana::Europeana::new(wskey)) }
+    }
+
+    /// ジャパンサーチ
+    pub fn japan_search() -> Self {
+        Self { provider: Box::new(japan_search::JapanSearch::new()) }
+    }
+
+    pub fn name(&self) -> String {
+        self.provider.name().to_string()
+    }
+
+    /// 扱えない条件があればエラーを投げる
+    pub fn url(&self, query: &SearchQuery) -> Result<String, JsValue> {
+        self.provider.url(query).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// レスポンスを読み込む
+    /// 読み込めなければエラーを投げる
+    pub fn parse(&self, response: String) -> Result<SearchResults, JsValue> {
+        self.provider.parse(&response).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 }
 
+/// 複数のサービスの検索結果の並べ方
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ranking {
+    /// 重みに応じて交互に並べる
+    Interleave,
+    /// サービスの順に並べる
+    Sequential,
+}
+
+/// 複数のサービスを横断する検索
+/// 取得はJSが並行して行い、レスポンスを受け取ってまとめる
+#[wasm_bindgen]
+pub struct FederatedSearch {
+    providers: Vec<Box<dyn SearchProvider>>,
+    /// サービスごとの重み
+    weights: Vec<f64>,
+    ranking: Ranking,
+    /// サービスごとの検索結果
+    responses: Vec<Option<Result<SearchResults, String>>>,
+}
+
+#[wasm_bindgen]
+impl FederatedSearch {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { providers: Vec::new(), weights: Vec::new(), ranking: Ranking::Interleave, responses: Vec::new() }
+    }
+
+    /// サービスを追加する
+    pub fn add(&mut self, provider: WasmSearchProvider) {
+        self.providers.push(provider.provider);
+        self.weights.push(1.0);
+        self.responses.push(None);
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn name(&self, i: usize) -> Option<String> {
+        self.providers.get(i).map(|p| p.name().to_string())
+    }
+
+    pub fn set_ranking(&mut self, ranking: Ranking) {
+        self.ranking = ranking;
+    }
+
+    /// サービスの重みを設定する
+    /// 大きいほど上位に多く並ぶ
+    pub fn set_weight(&mut self, i: usize, weight: f64) -> bool {
+        match self.weights.get_mut(i) {
+            Some(w) if weight > 0.0 => {
+                *w = weight;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 新しい検索を始める
+    /// 前回のレスポンスをすべて捨て、サービスごとのURLを返す(JSON配列, 追加した順)
+    /// 条件を扱えないサービスは失敗として記録し、URLはnull
+    pub fn start(&mut self, query: &SearchQuery) -> String {
+        let mut urls = Vec::new();
+        for (provider, response) in self.providers.iter().zip(self.responses.iter_mut()) {
+            match provider.url(query) {
+                Ok(url) => {
+                    *response = None;
+                    urls.push(Some(url));
+                }
+                Err(e) => {
+                    *response = Some(Err(e.to_string()));
+                    urls.push(None);
+                }
+            }
+        }
+        serde_json::to_string(&urls).unwrap_or(String::new())
+    }
+
+    /// i番目のサービスのレスポンスを受け取る
+    /// 読み込めなければ失敗として記録する
+    pub fn receive(&mut self, i: usize, response: String) -> bool {
+        let parsed = match self.providers.get(i) {
+            Some(provider) => provider.parse(&response).map_err(|e| e.to_string()),
+            None => return false,
+        };
+        let ok = parsed.is_ok();
+        self.responses[i] = Some(parsed);
+        ok
+    }
+
+    /// i番目のサービスの取得に失敗したことを記録する
+    pub fn fail(&mut self, i: usize, message: String) {
+        if let Some(response) = self.responses.get_mut(i) {
+            *response = Some(Err(message));
+        }
+    }
+
+    /// すべてのサービスのレスポンスを受け取ったか否か
+    pub fn is_complete(&self) -> bool {
+        self.responses.iter().all(Option::is_some)
+    }
+
+    /// 失敗したサービスと理由(JSON配列)
+    /// `[{"provider": "Japan Search", "message": "..."}, ...]`
+    pub fn failures(&self) -> String {
+        let failures: Vec<_> = self.responses.iter().enumerate()
+            .filter_map(|(i, response)| match response {
+                Some(Err(message)) => Some(serde_json::json!({
+                    "provider": self.providers[i].name(),
+                    "message": message,
+                })),
+                _ => None,
+            })
+            .collect();
+        serde_json::to_string(&failures).unwrap_or(String::new())
+    }
+
+    /// 受け取った検索結果をまとめる
+    /// 同じManifest(無ければURL)の結果は先に並んだものだけを残す
+    pub fn results(&self) -> SearchResults {
+        let results = self.responses.iter().enumerate()
+            .filter_map(|(i, response)| match response {
+                Some(Ok(results)) => Some((self.providers[i].name(), self.weights[i], results)),
+                _ => None,
+            })
+            .collect();
+        merge(results, self.ranking)
+    }
+}
+
+/// サービスごとの検索結果(名前, 重み, 結果)をまとめる
+fn merge(results: Vec<(&str, f64, &SearchResults)>, ranking: Ranking) -> SearchResults {
+    // (順位, サービスの順番, 結果)
+    let mut ranked: Vec<(f64, usize, SearchResult)> = Vec::new();
+    for (order, (name, weight, results)) in results.iter().enumerate() {
+        for (position, result) in results.results.iter().enumerate() {
+            let rank = match ranking {
+                Ranking::Interleave => position as f64 / weight,
+                Ranking::Sequential => order as f64,
+            };
+            let mut result = result.clone();
+            result.provider = Some(name.to_string());
+            ranked.push((rank, order, result));
+        }
+    }
+    ranked.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal).then(a.1.cmp(&b.1)));
+
+    let count = ranked.len();
+    let mut keys = std::collections::HashSet::new();
+    let merged: Vec<SearchResult> = ranked.into_iter()
+        .map(|(_, _, result)| result)
+        .filter(|result| keys.insert(result.manifest.clone().unwrap_or(result.url.clone())))
+        .collect();
+    // 受け取った中で見つかった重複の分を引く(まだ取得していない分の重複は分からない)
+    let duplicates = (count - merged.len()) as u32;
+    let total = results.iter().map(|(_, _, results)| results.total).sum::<u32>().saturating_sub(duplicates);
+    SearchResults { results: merged, total, next_cursor: None, facets: Vec::new() }
+}
+
+/// 検索履歴と名前を付けて保存した検索(localStorageに保存する)
+#[wasm_bindgen]
+pub struct SearchHistory {
+    library: SearchLibrary<LocalStorage>,
+}
+
+#[wasm_bindgen]
+impl SearchHistory {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { library: SearchLibrary::new(LocalStorage::new()) }
+    }
+
+    /// 検索したクエリと結果の件数を履歴に加える
+    pub fn record(&mut self, query: &SearchQuery, total: u32) -> bool {
+        let result = self.library.record(query, total, js_sys::Date::now());
+        Self::logged("record search", result)
+    }
+
+    /// 検索履歴(JSON配列, 新しい順)
+    /// `[{"query": {...}, "total": 12, "time": 1565000000000}, ...]`
+    pub fn history(&self) -> String {
+        serde_json::to_string(&self.library.history()).unwrap_or(String::new())
+    }
+
+    /// 履歴のクエリ(再検索用)
+    pub fn history_query(&self, index: usize) -> Option<SearchQuery> {
+        self.library.history().into_iter().nth(index).map(|entry| entry.query)
+    }
+
+    pub fn remove_history(&mut self, index: usize) -> bool {
+        let result = self.library.remove_history(index);
+        Self::logged("remove search history", result)
+    }
+
+    pub fn clear_history(&mut self) -> bool {
+        let result = self.library.clear_history();
+        Self::logged("clear search history", result)
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.library.history_limit()
+    }
+
+    pub fn set_history_limit(&mut self, limit: usize) -> bool {
+        let result = self.library.set_history_limit(limit);
+        Self::logged("limit search history", result)
+    }
+
+    /// 名前を付けて保存する(同じ名前は上書き)
+    pub fn save(&mut self, name: String, query: &SearchQuery) -> bool {
+        let result = self.library.save(&name, query, js_sys::Date::now());
+        Self::logged(&format!("save search {}", name), result)
+    }
+
+    /// 保存した検索の名前(JSON配列, 保存順)
+    pub fn saved_names(&self) -> String {
+        serde_json::to_string(&self.library.saved_names()).unwrap_or(String::new())
+    }
+
+    /// 保存した検索を読み込む(再検索用)
+    pub fn load(&self, name: String) -> Option<SearchQuery> {
+        match self.library.load(&name) {
+            Ok(query) => Some(query),
+            Err(e) => {
+                log(&format!("Cannot load search {}: {:?}", name, e));
+                None
+            }
+        }
+    }
+
+    pub fn delete(&mut self, name: String) -> bool {
+        let result = self.library.delete(&name);
+        Self::logged(&format!("delete search {}", name), result)
+    }
+}
+
+impl SearchHistory {
+    fn logged(action: &str, result: Result<(), StorageError>) -> bool {
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                log(&format!("Cannot {}: {:?}", action, e));
+                false
+            }
+        }
+    }
+}
+
+/// Europeana Search API
+/// [参考](https://pro.europeana.eu/resources/apis/search)
+pub mod europeana {
+    use super::*;
+    use std::collections::HashMap;
+
+    pub struct Europeana {
+        wskey: String,
+    }
+
+    impl Europeana {
+        pub fn new(wskey: String) -> Self {
+            Self { wskey }
+        }
+    }
+
+    impl SearchProvider for Europeana {
+        fn name(&self) -> &str {
+            "Europeana"
+        }
+
+        fn url(&self, query: &SearchQuery) -> Result<String, SearchError> {
+            Ok(url(query, &self.wskey))
+        }
+
+        fn parse(&self, response: &str) -> Result<SearchResults, SearchError> {
+            parse(response)
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<SearchResults, SearchError> {
+        let response: Response = serde_json::from_str(s)?;
+        response.into_results()
+    }
+
+    /// 言語ごとの値
+    type LangAware = HashMap<String, Vec<String>>;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Response {
+        success: bool,
+        #[serde(default)]
+        error: Option<String>,
+        #[serde(default)]
+        items: Vec<Item>,
+        #[serde(default)]
+        total_results: u32,
+        #[serde(default)]
+        next_cursor: Option<String>,
+        #[serde(default)]
+        facets: Vec<Facet>,
+    }
+
+    impl Response {
+        pub fn into_results(self) -> Result<SearchResults, SearchError> {
+            if !self.success {
+                return Err(SearchError::Api(self.error.unwrap_or(String::new())));
+            }
+            let results = self.items.into_iter().map(Item::into_result).collect();
+            Ok(SearchResults { results, total: self.total_results, next_cursor: self.next_cursor, facets: self.facets })
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Item {
+        id: String,
+        #[serde(default)]
+        title: Vec<String>,
+        #[serde(default)]
+        dc_title_lang_aware: LangAware,
+        #[serde(default)]
+        dc_description: Vec<String>,
+        #[serde(default)]
+        dc_description_lang_aware: LangAware,
+        #[serde(default)]
+        edm_preview: Vec<String>,
+        #[serde(default)]
+        edm_is_shown_by: Vec<String>,
+        #[serde(default)]
+        edm_is_shown_at: Vec<String>,
+        #[serde(default)]
+        guid: Option<String>,
+    }
+
+    impl Item {
+        fn into_result(self) -> SearchResult {
+            // Europeanaはメディアのあるレコードについて、idからIIIF Manifestを提供している
+            let manifest = if self.edm_is_shown_by.is_empty() {
+                None
+            } else {
+                Some(format!("https://iiif.europeana.eu/presentation{}/manifest", self.id))
+            };
+            let url = self.guid.clone()
+                .or(self.edm_is_shown_at.first().cloned())
+                .unwrap_or(format!("https://www.europeana.eu/item{}", self.id));
+
+            SearchResult {
+                url,
+                title: first(&self.title, &self.dc_title_lang_aware),
+                description: first(&self.dc_description, &self.dc_description_lang_aware),
+                thumbnail: self.edm_preview.first().cloned(),
+                manifest,
+                provider: None,
+            }
+        }
+    }
+
+    const SEARCH_URL: &str = "https://api.europeana.eu/record/v2/search.json";
+
+    /// 検索クエリからSearch APIのURLを作る
+    pub fn url(query: &SearchQuery, wskey: &str) -> String {
+        let mut params = vec![
+            ("wskey", wskey.to_string()),
+            ("query", query.query.clone()),
+            ("rows", query.rows.to_string()),
+        ];
+        match &query.cursor {
+            Some(cursor) => params.push(("cursor", cursor.clone())),
+            None => params.push(("start", query.start.to_string())),
+        }
+        if !query.theme.is_empty() {
+            params.push(("theme", query.theme.clone()));
+        }
+        if !query.sort.is_empty() {
+            params.push(("sort", query.sort.replace('+', " ")));
+        }
+        for refinement in &query.filters {
+            params.push(param(refinement));
+        }
+        if !query.facets.is_empty() {
+            params.push(("profile", "facets".to_string()));
+            params.push(("facet", query.facets.join(",")));
+        }
+
+        format!("{}?{}", SEARCH_URL, encode_params(&params))
+    }
+
+    /// 絞り込み条件をSearch APIのパラメータにする
+    fn param(refinement: &Refinement) -> (&'static str, String) {
+        match refinement {
+            Refinement::Type(t) => ("qf", format!("TYPE:{}", t)),
+            Refinement::Country(c) => ("qf", format!("COUNTRY:{}", quote(c))),
+            Refinement::Provider(p) => ("qf", format!("PROVIDER:{}", quote(p))),
+            Refinement::DataProvider(p) => ("qf", format!("DATA_PROVIDER:{}", quote(p))),
+            Refinement::Year(from, to) => ("qf", format!("YEAR:[{} TO {}]", from, to)),
+            Refinement::Rights(r) => ("qf", format!("RIGHTS:{}", quote(r))),
+            Refinement::Reusability(r) => ("reusability", r.clone()),
+            Refinement::Media(m) => ("media", m.to_string()),
+            Refinement::Iiif => ("qf", "sv_dcterms_conformsTo:*iiif*".to_string()),
+            Refinement::Field(field, value) => ("qf", format!("{}:{}", field, quote(value))),
+            // qf以外(再利用の可否など)は否定できない
+            Refinement::Not(refinement) => match param(refinement) {
+                ("qf", value) => ("qf", format!("-{}", value)),
+                param => param,
+            },
+        }
+    }
+
+    /// 空白などを含む値を""で囲む
+    fn quote(value: &str) -> String {
+        if value.chars().any(|c| c.is_whitespace() || c == ':') {
+            format!("\"{}\"", value.replace('"', "\\\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// 最初の値を取り出す
+    /// 無ければ言語ごとの値から def, en, その他の順に探す
+    fn first(values: &Vec<String>, lang_aware: &LangAware) -> String {
+        if let Some(value) = values.first() {
+            return value.clone();
+        }
+        let mut langs: Vec<&String> = lang_aware.keys().collect();
+        langs.sort();
+        ["def", "en"].iter()
+            .filter_map(|lang| lang_aware.get(*lang))
+            .chain(langs.into_iter().filter_map(|lang| lang_aware.get(lang)))
+            .filter_map(|values| values.first())
+            .next()
+            .cloned()
+            .unwrap_or(String::new())
+    }
+}
+
+
+/// ジャパンサーチ 横断検索API
+/// [参考](https://jpsearch.go.jp/api)
+pub mod japan_search {
+    use super::*;
+
+    const SEARCH_URL: &str = "https://jpsearch.go.jp/api/item/search/jps-cross";
+
+    pub struct JapanSearch;
+
+    impl JapanSearch {
+        pub fn new() -> Self {
+            JapanSearch
+        }
+    }
+
+    impl SearchProvider for JapanSearch {
+        fn name(&self) -> &str {
+            "Japan Search"
+        }
+
+        /// Europeana固有の条件(国、再利用の可否など)は無視する
+        /// 否定とEuropeanaの項目での絞り込みはできないのでエラー
+        fn url(&self, query: &SearchQuery) -> Result<String, SearchError> {
+            let mut params = vec![
+                ("keyword".to_string(), query.query.clone()),
+                ("size".to_string(), query.rows.to_string()),
+                ("from".to_string(), (query.start - 1).to_string()),
+            ];
+            for refinement in &query.filters {
+                let param = |key: &str, value: String| (key.to_string(), value);
+                match refinement {
+                    Refinement::Type(t) => params.push(param("f-type", t.to_lowercase())),
+                    Refinement::DataProvider(db) => params.push(param("f-db", db.clone())),
+                    Refinement::Provider(p) => params.push(param("f-provider", p.clone())),
+                    Refinement::Year(from, to) => params.push(param("r-tempo", format!("{}-{}", from, to))),
+                    Refinement::Iiif => params.push(param("f-contents", "iiif_manifest".to_string())),
+                    Refinement::Field(..) | Refinement::Not(_) => return Err(SearchError::Unsupported(self.name().to_string(), syntax::format_refinement(refinement))),
+                    _ => {}
+                }
+            }
+            Ok(format!("{}?{}", SEARCH_URL, encode_params(&params)))
+        }
+
+        fn parse(&self, response: &str) -> Result<SearchResults, SearchError> {
+            let response: Response = serde_json::from_str(response)?;
+            Ok(response.into_results())
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Response {
+        #[serde(default)]
+        hit: u32,
+        #[serde(default)]
+        list: Vec<Item>,
+    }
+
+    impl Response {
+        fn into_results(self) -> SearchResults {
+            let results = self.list.into_iter().map(Item::into_result).collect();
+            SearchResults { results, total: self.hit, next_cursor: None, facets: Vec::new() }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Item {
+        id: String,
+        common: Common,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Common {
+        #[serde(default)]
+        title: String,
+        #[serde(default)]
+        contributor: Vec<String>,
+        #[serde(default)]
+        description: Vec<String>,
+        #[serde(default)]
+        thumbnail_url: Vec<String>,
+        #[serde(default)]
+        link_url: Option<String>,
+        #[serde(default)]
+        iiif_manifest: Option<String>,
+    }
+
+    impl Item {
+        fn into_result(self) -> SearchResult {
+            let Common { title, contributor, description, thumbnail_url, link_url, iiif_manifest } = self.common;
+            let url = link_url.unwrap_or(format!("https://jpsearch.go.jp/item/{}", self.id));
+            let description = contributor.into_iter().chain(description).collect::<Vec<_>>().join(" / ");
+
+            SearchResult {
+                url,
+                title,
+                description,
+                thumbnail: thumbnail_url.into_iter().next(),
+                manifest: iiif_manifest,
+                provider: None,
+            }
+        }
+    }
+}
 
 /// サーバーに投げる検索クエリ
 /// [参考](https://pro.europeana.eu/resources/apis/search) ## Getting Started
 #[wasm_bindgen]
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
+// 古い形式で保存したクエリに無い項目は既定値にする
+#[serde(default)]
 pub struct SearchQuery {
     query: String,
     theme: String,
     sort: String,
     rows: u8,
+    /// 何件目から取得するか(1始まり)
+    start: u32,
+    /// cursorによるページ送りのcursor
+    /// 最初のページは"*"
+    cursor: Option<String>,
+    /// 絞り込み条件
+    filters: Vec<Refinement>,
+    /// 件数を知りたい項目(TYPE, COUNTRYなど)
+    facets: Vec<String>,
+}
+
+/// 検索の絞り込み条件
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Refinement {
+    /// 資料の種類(IMAGE, TEXTなど)
+    Type(String),
+    Country(String),
+    /// 集約した機関
+    Provider(String),
+    /// 資料を所蔵する機関
+    DataProvider(String),
+    /// 年の範囲
+    Year(u32, u32),
+    /// 権利表記のURL
+    Rights(String),
+    /// 再利用の可否(open, restricted, permission)
+    Reusability(String),
+    /// メディアを持つか否か
+    Media(bool),
+    /// IIIFに対応しているもの
+    Iiif,
+    /// その他の項目
+    Field(String, String),
+    /// 条件に当てはまらないもの
+    Not(Box<Refinement>),
+}
+
+impl Refinement {
+    /// 同時に1つしか指定できない条件か否か
+    fn is_single(&self) -> bool {
+        match self {
+            Refinement::Year(..) | Refinement::Reusability(_) | Refinement::Media(_) | Refinement::Iiif => true,
+            _ => false,
+        }
+    }
+
+    /// 同じ種類の条件か否か
+    fn same_kind(&self, other: &Refinement) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
 }
 
 #[wasm_bindgen]
 impl SearchQuery {
     #[wasm_bindgen(constructor)]
     pub fn new(query: String) -> Self {
-        Self { query, theme: String::new(), sort: String::new(), rows: 10 }
+        Self { query, theme: String::new(), sort: String::new(), rows: 10, start: 1, cursor: None, filters: Vec::new(), facets: Vec::new() }
     }
 
     pub fn set_theme(&mut self, theme: String) {
@@ -112,8 +1248,489 @@ impl SearchQuery {
         self.rows.clone()
     }
 
+    pub fn set_start(&mut self, start: u32) {
+        self.start = start.max(1);
+    }
+
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// cursorによるページ送りにする
+    /// Noneならstartによるページ送りに戻す
+    pub fn set_cursor(&mut self, cursor: Option<String>) {
+        self.cursor = cursor;
+    }
+
+    pub fn cursor(&self) -> Option<String> {
+        self.cursor.clone()
+    }
+
+    /// このクエリの検索結果から、次のページを取得するクエリを作る
+    /// 次のページが無ければNone
+    pub fn next_page(&self, results: &SearchResults) -> Option<SearchQuery> {
+        let mut next = self.clone();
+        if self.cursor.is_some() {
+            next.cursor = Some(results.next_cursor.clone()?);
+        } else {
+            next.start = self.start + self.rows as u32;
+            if next.start > results.total {
+                return None;
+            }
+        }
+        Some(next)
+    }
+
+    pub fn add_type(&mut self, type_: String) {
+        self.add_filter(Refinement::Type(type_));
+    }
+
+    pub fn add_country(&mut self, country: String) {
+        self.add_filter(Refinement::Country(country));
+    }
+
+    pub fn add_provider(&mut self, provider: String) {
+        self.add_filter(Refinement::Provider(provider));
+    }
+
+    pub fn add_data_provider(&mut self, data_provider: String) {
+        self.add_filter(Refinement::DataProvider(data_provider));
+    }
+
+    pub fn add_rights(&mut self, rights: String) {
+        self.add_filter(Refinement::Rights(rights));
+    }
+
+    /// 任意の項目で絞り込む
+    pub fn add_field(&mut self, field: String, value: String) {
+        self.add_filter(Refinement::Field(field, value));
+    }
+
+    /// 年の範囲で絞り込む
+    pub fn set_year_range(&mut self, from: u32, to: u32) {
+        let (from, to) = if from <= to { (from, to) } else { (to, from) };
+        self.add_filter(Refinement::Year(from, to));
+    }
+
+    /// 再利用の可否で絞り込む
+    /// Noneなら絞り込まない
+    pub fn set_reusability(&mut self, reusability: Option<String>) {
+        match reusability {
+            Some(r) => self.add_filter(Refinement::Reusability(r)),
+            None => self.remove_kind(&Refinement::Reusability(String::new())),
+        }
+    }
+
+    /// メディアを持つもので絞り込む
+    pub fn set_media(&mut self, media: bool) {
+        match media {
+            true => self.add_filter(Refinement::Media(true)),
+            false => self.remove_kind(&Refinement::Media(true)),
+        }
+    }
+
+    /// IIIFに対応しているもので絞り込む
+    pub fn set_has_iiif(&mut self, iiif: bool) {
+        match iiif {
+            true => self.add_filter(Refinement::Iiif),
+            false => self.remove_kind(&Refinement::Iiif),
+        }
+    }
+
+    /// 検索結果の項目(facet)の値で絞り込む
+    pub fn refine(&mut self, facet: String, label: String) {
+        let refinement = match facet.as_str() {
+            "TYPE" => Refinement::Type(label),
+            "COUNTRY" => Refinement::Country(label),
+            "PROVIDER" => Refinement::Provider(label),
+            "DATA_PROVIDER" => Refinement::DataProvider(label),
+            "RIGHTS" => Refinement::Rights(label),
+            "YEAR" => match label.parse() {
+                Ok(year) => Refinement::Year(year, year),
+                Err(_) => Refinement::Field(facet, label),
+            },
+            _ => Refinement::Field(facet, label),
+        };
+        self.add_filter(refinement);
+    }
+
+    pub fn remove_filter(&mut self, index: usize) -> bool {
+        if index < self.filters.len() {
+            self.filters.remove(index);
+            true
+        } else { false }
+    }
+
+    pub fn clear_filters(&mut self) {
+        self.filters.clear();
+    }
+
+    /// 絞り込み条件(JSON配列)
+    pub fn filters(&self) -> String {
+        serde_json::to_string(&self.filters).unwrap_or(String::new())
+    }
+
+    /// 件数を知りたい項目を追加する
+    pub fn add_facet(&mut self, facet: String) {
+        if !self.facets.contains(&facet) {
+            self.facets.push(facet);
+        }
+    }
+
+    pub fn clear_facets(&mut self) {
+        self.facets.clear();
+    }
+
+    /// Europeana Search APIのURL
+    pub fn europeana_url(&self, wskey: String) -> String {
+        europeana::url(self, &wskey)
+    }
+
     pub fn json(&self) -> String {
         serde_json::to_string(&self).unwrap_or(String::new())
     }
+
+    /// `hokusai type:image year:1800-1850 provider:"BnF" -country:france` のような文字列を読み込む
+    /// 読み込めなければエラー(理由)を投げる
+    pub fn parse(s: String) -> Result<SearchQuery, JsValue> {
+        syntax::parse(&s).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// 検索欄に表示する文字列(parseの逆)
+    pub fn to_query_string(&self) -> String {
+        syntax::format(self)
+    }
+}
+
+impl SearchQuery {
+    /// ページ送りを除いて同じ検索か否か
+    pub fn same_search(&self, other: &SearchQuery) -> bool {
+        self.query == other.query && self.theme == other.theme && self.sort == other.sort && self.filters == other.filters
+    }
+
+    /// 最初のページを取得するクエリ
+    pub fn first_page(&self) -> SearchQuery {
+        let mut first = self.clone();
+        first.start = 1;
+        if first.cursor.is_some() {
+            first.cursor = Some("*".to_string());
+        }
+        first
+    }
+
+    /// 絞り込み条件を追加する
+    /// 同時に1つしか指定できない条件は置き換える
+    fn add_filter(&mut self, refinement: Refinement) {
+        if refinement.is_single() {
+            self.remove_kind(&refinement);
+        }
+        if !self.filters.contains(&refinement) {
+            self.filters.push(refinement);
+        }
+    }
+
+    /// 同じ種類の条件を取り除く
+    fn remove_kind(&mut self, refinement: &Refinement) {
+        self.filters.retain(|f| !f.same_kind(refinement));
+    }
+}
+
+/// 検索欄に入力する文字列の書式
+///
+/// - `word`, `"phrase"`: 検索語
+/// - `field:value`, `field:"value"`: 絞り込み(type, country, provider, data_provider, rights, year, reuse, media, has, who, what など)
+///   知らない項目(`http://...` など)は `"field:value"` の検索語にする
+/// - `year:1800-1850`: 年の範囲
+/// - `-word`, `-field:value`: 否定
+pub mod syntax {
+    use super::*;
+
+    /// 読み込めなかった理由
+    #[derive(Debug, PartialEq)]
+    pub enum SyntaxError {
+        /// 閉じていない""(開始位置, 1始まり)
+        UnclosedQuote(usize),
+        /// `field:` の後に値が無い
+        EmptyValue(String),
+        /// 年(範囲)が読めない
+        InvalidYear(String),
+        /// 値が選択肢に無い(項目, 値, 選択肢)
+        InvalidValue(String, String, &'static str),
+        /// 否定できない項目
+        CannotNegate(String),
+    }
+
+    impl fmt::Display for SyntaxError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                SyntaxError::UnclosedQuote(position) =>
+                    write!(f, "The quote at character {} is not closed", position),
+                SyntaxError::EmptyValue(field) =>
+                    write!(f, "\"{}:\" needs a value", field),
+                SyntaxError::InvalidYear(value) =>
+                    write!(f, "\"{}\" is not a year: use e.g. year:1800 or year:1800-1850", value),
+                SyntaxError::InvalidValue(field, value, expected) =>
+                    write!(f, "\"{}\" is not allowed for {}: use one of {}", value, field, expected),
+                SyntaxError::CannotNegate(field) =>
+                    write!(f, "{} cannot be negated with \"-\"", field),
+            }
+        }
+    }
+
+    /// 空白で区切った1語
+    struct Term {
+        negated: bool,
+        field: Option<String>,
+        value: String,
+        quoted: bool,
+    }
+
+    pub fn parse(s: &str) -> Result<SearchQuery, SyntaxError> {
+        let mut words = Vec::new();
+        let mut refinements = Vec::new();
+        for term in terms(s)? {
+            match &term.field {
+                Some(field) if !is_field(field) => {
+                    let word = quote(&format!("{}:{}", field, term.value));
+                    words.push(if term.negated { format!("-{}", word) } else { word });
+                }
+                Some(field) => {
+                    let refinement = match refinement(field, &term.value)? {
+                        Some(refinement) => refinement,
+                        None => continue,
+                    };
+                    refinements.push(if term.negated {
+                        match refinement {
+                            Refinement::Reusability(_) | Refinement::Media(_) => return Err(SyntaxError::CannotNegate(field.clone())),
+                            refinement => Refinement::Not(Box::new(refinement)),
+                        }
+                    } else { refinement });
+                }
+                None if term.value.is_empty() => {}
+                None => {
+                    let word = if term.quoted { quote(&term.value) } else { term.value.clone() };
+                    words.push(if term.negated { format!("-{}", word) } else { word });
+                }
+            }
+        }
+
+        let mut query = SearchQuery::new(words.join(" "));
+        for refinement in refinements {
+            query.add_filter(refinement);
+        }
+        Ok(query)
+    }
+
+    /// 検索語と絞り込み条件を文字列にする
+    pub fn format(query: &SearchQuery) -> String {
+        let mut terms = Vec::new();
+        if !query.query.is_empty() {
+            terms.push(query.query.clone());
+        }
+        terms.extend(query.filters.iter().map(format_refinement));
+        terms.join(" ")
+    }
+
+    pub(crate) fn format_refinement(refinement: &Refinement) -> String {
+        match refinement {
+            Refinement::Type(t) => format!("type:{}", quote_if_needed(t)),
+            Refinement::Country(c) => format!("country:{}", quote_if_needed(c)),
+            Refinement::Provider(p) => format!("provider:{}", quote_if_needed(p)),
+            Refinement::DataProvider(p) => format!("data_provider:{}", quote_if_needed(p)),
+            Refinement::Year(from, to) if from == to => format!("year:{}", from),
+            Refinement::Year(from, to) => format!("year:{}-{}", from, to),
+            Refinement::Rights(r) => format!("rights:{}", quote_if_needed(r)),
+            Refinement::Reusability(r) => format!("reuse:{}", quote_if_needed(r)),
+            Refinement::Media(m) => format!("media:{}", m),
+            Refinement::Iiif => "has:iiif".to_string(),
+            Refinement::Field(field, value) => format!("{}:{}", field, quote_if_needed(value)),
+            Refinement::Not(refinement) => format!("-{}", format_refinement(refinement)),
+        }
+    }
+
+    /// 絞り込みに使う項目
+    const REFINEMENTS: &[&str] = &["type", "country", "provider", "data_provider", "dataprovider", "rights", "year",
+        "reuse", "reusability", "media", "iiif", "has"];
+    /// Europeanaの検索で使える項目
+    const FIELDS: &[&str] = &["who", "what", "when", "where", "title", "subject", "creator", "contributor",
+        "description", "language"];
+
+    /// 絞り込みの項目か否か
+    /// 検索結果の項目(`LANGUAGE` など大文字のfacet)もそのまま使う
+    fn is_field(field: &str) -> bool {
+        let lower = field.to_lowercase();
+        REFINEMENTS.contains(&lower.as_str()) || FIELDS.contains(&lower.as_str())
+            || field.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+    }
+
+    /// 項目と値から絞り込み条件を作る
+    /// 条件にならないもの(`iiif:false` など)はNone
+    fn refinement(field: &str, value: &str) -> Result<Option<Refinement>, SyntaxError> {
+        let refinement = match field.to_lowercase().as_str() {
+            "type" => Refinement::Type(value.to_uppercase()),
+            "country" => Refinement::Country(value.to_string()),
+            "provider" => Refinement::Provider(value.to_string()),
+            "data_provider" | "dataprovider" => Refinement::DataProvider(value.to_string()),
+            "rights" => Refinement::Rights(value.to_string()),
+            "year" => {
+                let (from, to) = year_range(value).ok_or_else(|| SyntaxError::InvalidYear(value.to_string()))?;
+                Refinement::Year(from.min(to), from.max(to))
+            }
+            "reuse" | "reusability" => match value.to_lowercase().as_str() {
+                r @ "open" | r @ "restricted" | r @ "permission" => Refinement::Reusability(r.to_string()),
+                _ => return Err(SyntaxError::InvalidValue(field.to_string(), value.to_string(), "open, restricted, permission")),
+            },
+            "media" => Refinement::Media(boolean(field, value)?),
+            "iiif" => match boolean(field, value)? {
+                true => Refinement::Iiif,
+                false => return Ok(None),
+            },
+            "has" => match value.to_lowercase().as_str() {
+                "iiif" => Refinement::Iiif,
+                "media" => Refinement::Media(true),
+                _ => return Err(SyntaxError::InvalidValue(field.to_string(), value.to_string(), "iiif, media")),
+            },
+            _ => Refinement::Field(field.to_string(), value.to_string()),
+        };
+        Ok(Some(refinement))
+    }
+
+    /// `1800` または `1800-1850`
+    fn year_range(value: &str) -> Option<(u32, u32)> {
+        let mut years = value.splitn(2, '-').map(|year| year.trim().parse::<u32>());
+        let from = years.next()?.ok()?;
+        let to = match years.next() {
+            Some(to) => to.ok()?,
+            None => from,
+        };
+        Some((from, to))
+    }
+
+    fn boolean(field: &str, value: &str) -> Result<bool, SyntaxError> {
+        match value.to_lowercase().as_str() {
+            "true" | "yes" => Ok(true),
+            "false" | "no" => Ok(false),
+            _ => Err(SyntaxError::InvalidValue(field.to_string(), value.to_string(), "true, false")),
+        }
+    }
+
+    /// 空白で区切る(""の中は区切らない)
+    fn terms(s: &str) -> Result<Vec<Term>, SyntaxError> {
+        let chars: Vec<char> = s.chars().collect();
+        let mut terms = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i].is_whitespace() {
+                i += 1;
+                continue;
+            }
+            let negated = chars[i] == '-' && chars.get(i + 1).map(|c| !c.is_whitespace()).unwrap_or(false);
+            if negated {
+                i += 1;
+            }
+
+            // field:
+            let mut field = None;
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            if i > start && chars.get(i) == Some(&':') {
+                field = Some(chars[start..i].iter().collect::<String>());
+                i += 1;
+            } else {
+                i = start;
+            }
+
+            // value
+            let quoted = chars.get(i) == Some(&'"');
+            let mut value = String::new();
+            if quoted {
+                let open = i;
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(SyntaxError::UnclosedQuote(open + 1)),
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            i += 1;
+                            value.push(chars[i]);
+                        }
+                        Some(&c) => value.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+            } else {
+                while i < chars.len() && !chars[i].is_whitespace() {
+                    value.push(chars[i]);
+                    i += 1;
+                }
+            }
+
+            if let Some(field) = &field {
+                if value.trim().is_empty() {
+                    return Err(SyntaxError::EmptyValue(field.clone()));
+                }
+            }
+            terms.push(Term { negated, field, value, quoted });
+        }
+        Ok(terms)
+    }
+
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    /// 空白などを含む値を""で囲む
+    fn quote_if_needed(value: &str) -> String {
+        if value.is_empty() || value.chars().any(|c| c.is_whitespace() || c == '"') {
+            quote(value)
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+/// パラメータをURLのクエリにする
+fn encode_params<K: AsRef<str>>(params: &Vec<(K, String)>) -> String {
+    params.iter()
+        .map(|(key, value)| format!("{}={}", key.as_ref(), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// URLのクエリをデコードする
+pub fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// URLのクエリに使えるようにエンコードする
+pub fn encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
 }
 
@@ -0,0 +1,483 @@
+use crate::curation::CurationItem;
+use crate::search::SearchQuery;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::curation::test::item;
+
+    fn items(labels: &[&str]) -> Vec<CurationItem> {
+        labels.iter().map(|label| item(label)).collect()
+    }
+
+    #[test]
+    fn save_load_delete_test() {
+        let mut library = CurationLibrary::new(MemoryStorage::new());
+        library.save("hokusai", &items(&["a", "b"])).unwrap();
+        library.save("hiroshige", &items(&["c"])).unwrap();
+        library.save("hokusai", &items(&["a", "b", "d"])).unwrap();
+
+        assert_eq!(library.names(), vec!["hokusai", "hiroshige"]);
+        assert_eq!(library.load("hokusai").unwrap().len(), 3);
+        assert_eq!(library.last_opened(), Some("hokusai".to_string()));
+
+        library.delete("hokusai").unwrap();
+        assert_eq!(library.names(), vec!["hiroshige"]);
+        assert_eq!(library.last_opened(), None);
+        assert!(library.load("hokusai").is_err());
+    }
+
+    #[test]
+    fn export_import_test() {
+        let mut library = CurationLibrary::new(MemoryStorage::new());
+        library.save("hokusai", &items(&["a", "b"])).unwrap();
+        library.save("hiroshige", &items(&["c"])).unwrap();
+        let json = library.export().unwrap();
+
+        let mut other = CurationLibrary::new(MemoryStorage::new());
+        other.save("hiroshige", &items(&["x", "y", "z"])).unwrap();
+        assert_eq!(other.import(&json).unwrap(), 2);
+        // 同じ名前のキュレーションは残し、番号を付けて読み込む
+        assert_eq!(other.names(), vec!["hiroshige", "hokusai", "hiroshige (2)"]);
+        assert_eq!(other.load("hiroshige").unwrap().len(), 3);
+        assert_eq!(other.load("hiroshige (2)").unwrap().len(), 1);
+        assert_eq!(other.unique_name("hiroshige"), "hiroshige (3)");
+        assert_eq!(other.unique_name("hiroshige (2)"), "hiroshige (2) (2)");
+        assert_eq!(other.unique_name("kuniyoshi"), "kuniyoshi");
+
+        assert!(other.import("{").is_err());
+    }
+
+    #[test]
+    fn search_history_test() {
+        let mut library = SearchLibrary::new(MemoryStorage::new());
+        library.record(&SearchQuery::new("hokusai".to_string()), 10, 1.0).unwrap();
+        library.record(&SearchQuery::new("hiroshige".to_string()), 5, 2.0).unwrap();
+        let mut next = SearchQuery::new("hokusai".to_string());
+        next.set_start(21);
+        library.record(&next, 12, 3.0).unwrap();
+
+        // 同じ検索は先頭に移る
+        let history = library.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].query.query(), "hokusai");
+        assert_eq!(history[0].query.start(), 1);
+        assert_eq!(history[0].total, 12);
+
+        library.set_history_limit(1).unwrap();
+        assert_eq!(library.history().len(), 1);
+        library.clear_history().unwrap();
+        assert!(library.history().is_empty());
+
+        // 上限は保存され、次に開いたときも使う
+        let library = SearchLibrary::new(library.storage);
+        assert_eq!(library.history_limit(), 1);
+        assert_eq!(SearchLibrary::new(MemoryStorage::new()).history_limit(), HISTORY_LIMIT);
+    }
+
+    #[test]
+    fn old_history_test() {
+        // 項目の足りない古い形式も読み、読めない項目だけを飛ばす
+        let mut storage = MemoryStorage::new();
+        storage.set(&SearchLibrary::<MemoryStorage>::history_key(),
+                    r#"[{"query": {"query": "hokusai"}, "total": 3}, {"broken": true}]"#).unwrap();
+        let mut library = SearchLibrary::new(storage);
+        let history = library.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].query.query(), "hokusai");
+        assert_eq!(history[0].query.rows(), 10);
+        assert_eq!(history[0].query.start(), 1);
+        assert_eq!(history[0].total, 3);
+
+        // 次の記録で古い履歴が消えない
+        library.record(&SearchQuery::new("hiroshige".to_string()), 5, 1.0).unwrap();
+        assert_eq!(library.history().len(), 2);
+    }
+
+    #[test]
+    fn saved_search_test() {
+        let mut library = SearchLibrary::new(MemoryStorage::new());
+        let mut query = SearchQuery::new("hokusai".to_string());
+        query.add_type("IMAGE".to_string());
+        library.save("wave", &query, 1.0).unwrap();
+        library.save("hiroshige", &SearchQuery::new("hiroshige".to_string()), 2.0).unwrap();
+        library.save("wave", &SearchQuery::new("great wave".to_string()), 3.0).unwrap();
+
+        assert_eq!(library.saved_names(), vec!["wave", "hiroshige"]);
+        assert_eq!(library.load("wave").unwrap().query(), "great wave");
+
+        library.delete("wave").unwrap();
+        assert_eq!(library.saved_names(), vec!["hiroshige"]);
+        assert!(library.load("wave").is_err());
+        assert!(library.delete("wave").is_err());
+    }
+}
+
+/// storageに保存するkeyの接頭辞
+const PREFIX: &str = "iiif-manga-viewer";
+
+/// 保存時のエラー
+#[derive(Debug)]
+pub enum StorageError {
+    /// storageが利用できない
+    Unavailable,
+    /// 書き込みに失敗した(容量超過など)
+    Write,
+    /// 指定された名前のキュレーション(保存した検索)が無い
+    NotFound(String),
+    /// JSONの読み書きに失敗した
+    Json(serde_json::Error),
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Json(e)
+    }
+}
+
+/// key-value形式の保存先
+pub trait Storage {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError>;
+    fn remove(&mut self, key: &str) -> Result<(), StorageError>;
+}
+
+/// ブラウザのlocalStorage
+pub struct LocalStorage {
+    storage: Option<web_sys::Storage>,
+}
+
+impl LocalStorage {
+    pub fn new() -> Self {
+        let storage = web_sys::window()
+            .and_then(|window| window.local_storage().ok())
+            .and_then(|storage| storage);
+        Self { storage }
+    }
+
+    fn storage(&self) -> Result<&web_sys::Storage, StorageError> {
+        self.storage.as_ref().ok_or(StorageError::Unavailable)
+    }
+}
+
+impl Storage for LocalStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        self.storage().ok()?.get_item(key).ok()?
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError> {
+        self.storage()?.set_item(key, value).map_err(|_| StorageError::Write)
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        self.storage()?.remove_item(key).map_err(|_| StorageError::Write)
+    }
+}
+
+/// メモリ上の保存先
+#[derive(Default)]
+pub struct MemoryStorage {
+    map: std::collections::HashMap<String, String>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<String> {
+        self.map.get(key).cloned()
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), StorageError> {
+        self.map.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), StorageError> {
+        self.map.remove(key);
+        Ok(())
+    }
+}
+
+/// ライブラリ全体を書き出したファイル
+#[derive(Serialize, Deserialize)]
+struct LibraryFile {
+    curations: Vec<NamedCuration>,
+}
+
+/// 名前付きのキュレーション
+#[derive(Serialize, Deserialize)]
+struct NamedCuration {
+    name: String,
+    items: Vec<CurationItem>,
+}
+
+/// 名前付きキュレーションの一覧
+pub struct CurationLibrary<S: Storage> {
+    storage: S,
+}
+
+impl<S: Storage> CurationLibrary<S> {
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    fn names_key() -> String {
+        format!("{}/curations", PREFIX)
+    }
+
+    fn curation_key(name: &str) -> String {
+        format!("{}/curation/{}", PREFIX, name)
+    }
+
+    fn last_opened_key() -> String {
+        format!("{}/last-opened", PREFIX)
+    }
+
+    /// 保存されているキュレーションの名前(保存順)
+    pub fn names(&self) -> Vec<String> {
+        self.storage.get(&Self::names_key())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn set_names(&mut self, names: &Vec<String>) -> Result<(), StorageError> {
+        let json = serde_json::to_string(names)?;
+        self.storage.set(&Self::names_key(), &json)
+    }
+
+    /// 保存されているキュレーションと重ならない名前
+    /// 重なるときは`name (2)`のように番号を付ける
+    pub fn unique_name(&self, name: &str) -> String {
+        let names = self.names();
+        let mut unique = name.to_string();
+        let mut n = 2;
+        while names.contains(&unique) {
+            unique = format!("{} ({})", name, n);
+            n += 1;
+        }
+        unique
+    }
+
+    pub fn load(&self, name: &str) -> Result<Vec<CurationItem>, StorageError> {
+        let json = self.storage.get(&Self::curation_key(name))
+            .ok_or_else(|| StorageError::NotFound(name.to_string()))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// 保存し、最後に開いたキュレーションとする
+    pub fn save(&mut self, name: &str, items: &Vec<CurationItem>) -> Result<(), StorageError> {
+        let json = serde_json::to_string(items)?;
+        self.storage.set(&Self::curation_key(name), &json)?;
+
+        let mut names = self.names();
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+            self.set_names(&names)?;
+        }
+        self.set_last_opened(name)
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), StorageError> {
+        self.storage.remove(&Self::curation_key(name))?;
+
+        let names = self.names().into_iter().filter(|n| n != name).collect();
+        self.set_names(&names)?;
+        if self.last_opened().as_ref().map(String::as_str) == Some(name) {
+            self.storage.remove(&Self::last_opened_key())?;
+        }
+        Ok(())
+    }
+
+    pub fn last_opened(&self) -> Option<String> {
+        self.storage.get(&Self::last_opened_key())
+    }
+
+    pub fn set_last_opened(&mut self, name: &str) -> Result<(), StorageError> {
+        self.storage.set(&Self::last_opened_key(), name)
+    }
+
+    /// すべてのキュレーションを1つのJSONに書き出す
+    pub fn export(&self) -> Result<String, StorageError> {
+        let mut curations = Vec::new();
+        for name in self.names() {
+            let items = self.load(&name)?;
+            curations.push(NamedCuration { name, items });
+        }
+        Ok(serde_json::to_string(&LibraryFile { curations })?)
+    }
+
+    /// 書き出したJSONを読み込む
+    /// 同じ名前のキュレーションは上書きせず、番号を付けた名前で保存する
+    pub fn import(&mut self, json: &str) -> Result<usize, StorageError> {
+        let last_opened = self.last_opened();
+        let file: LibraryFile = serde_json::from_str(json)?;
+        for curation in &file.curations {
+            let name = self.unique_name(&curation.name);
+            self.save(&name, &curation.items)?;
+        }
+        match last_opened {
+            Some(name) => self.set_last_opened(&name)?,
+            None => self.storage.remove(&Self::last_opened_key())?,
+        }
+        Ok(file.curations.len())
+    }
+}
+
+/// 検索履歴の1件
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub query: SearchQuery,
+    /// 検索結果の件数
+    #[serde(default)]
+    pub total: u32,
+    /// 検索した時刻(ms)
+    #[serde(default)]
+    pub time: f64,
+}
+
+/// 名前を付けて保存した検索
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: SearchQuery,
+    /// 保存した時刻(ms)
+    #[serde(default)]
+    pub created: f64,
+}
+
+/// 検索履歴の既定の上限
+const HISTORY_LIMIT: usize = 50;
+
+/// 検索履歴と保存した検索
+pub struct SearchLibrary<S: Storage> {
+    storage: S,
+    limit: usize,
+}
+
+impl<S: Storage> SearchLibrary<S> {
+    pub fn new(storage: S) -> Self {
+        let limit = storage.get(&Self::limit_key())
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(HISTORY_LIMIT);
+        Self { storage, limit }
+    }
+
+    fn history_key() -> String {
+        format!("{}/search-history", PREFIX)
+    }
+
+    fn limit_key() -> String {
+        format!("{}/search-history-limit", PREFIX)
+    }
+
+    fn saved_key() -> String {
+        format!("{}/saved-searches", PREFIX)
+    }
+
+    /// 検索履歴(新しい順)
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        read_entries(self.storage.get(&Self::history_key()))
+    }
+
+    fn set_history(&mut self, history: &Vec<HistoryEntry>) -> Result<(), StorageError> {
+        let json = serde_json::to_string(history)?;
+        self.storage.set(&Self::history_key(), &json)
+    }
+
+    /// 検索履歴に加える
+    /// 同じ検索が既にあれば先頭に移す
+    pub fn record(&mut self, query: &SearchQuery, total: u32, time: f64) -> Result<(), StorageError> {
+        let mut history: Vec<HistoryEntry> = self.history().into_iter()
+            .filter(|entry| !entry.query.same_search(query))
+            .collect();
+        history.insert(0, HistoryEntry { query: query.first_page(), total, time });
+        history.truncate(self.limit);
+        self.set_history(&history)
+    }
+
+    pub fn remove_history(&mut self, index: usize) -> Result<(), StorageError> {
+        let mut history = self.history();
+        if index < history.len() {
+            history.remove(index);
+        }
+        self.set_history(&history)
+    }
+
+    pub fn clear_history(&mut self) -> Result<(), StorageError> {
+        self.storage.remove(&Self::history_key())
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.limit
+    }
+
+    /// 検索履歴の上限を変える
+    /// 超えた分は古いものから捨てる
+    pub fn set_history_limit(&mut self, limit: usize) -> Result<(), StorageError> {
+        self.storage.set(&Self::limit_key(), &limit.to_string())?;
+        self.limit = limit;
+        let mut history = self.history();
+        if history.len() > limit {
+            history.truncate(limit);
+            self.set_history(&history)?;
+        }
+        Ok(())
+    }
+
+    /// 保存した検索(保存順)
+    pub fn saved(&self) -> Vec<SavedSearch> {
+        read_entries(self.storage.get(&Self::saved_key()))
+    }
+
+    fn set_saved(&mut self, saved: &Vec<SavedSearch>) -> Result<(), StorageError> {
+        let json = serde_json::to_string(saved)?;
+        self.storage.set(&Self::saved_key(), &json)
+    }
+
+    pub fn saved_names(&self) -> Vec<String> {
+        self.saved().into_iter().map(|s| s.name).collect()
+    }
+
+    pub fn load(&self, name: &str) -> Result<SearchQuery, StorageError> {
+        self.saved().into_iter()
+            .find(|s| s.name == name)
+            .map(|s| s.query)
+            .ok_or_else(|| StorageError::NotFound(name.to_string()))
+    }
+
+    /// 名前を付けて保存する
+    /// 同じ名前があれば上書きする
+    pub fn save(&mut self, name: &str, query: &SearchQuery, time: f64) -> Result<(), StorageError> {
+        let mut saved = self.saved();
+        let search = SavedSearch { name: name.to_string(), query: query.first_page(), created: time };
+        match saved.iter_mut().find(|s| s.name == name) {
+            Some(s) => *s = search,
+            None => saved.push(search),
+        }
+        self.set_saved(&saved)
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), StorageError> {
+        let mut saved = self.saved();
+        let len = saved.len();
+        saved.retain(|s| s.name != name);
+        if saved.len() == len {
+            return Err(StorageError::NotFound(name.to_string()));
+        }
+        self.set_saved(&saved)
+    }
+}
+
+/// 保存したJSON配列を読む
+/// 読めない項目だけを飛ばし、残りは上書きで失われないようにする
+fn read_entries<T: serde::de::DeserializeOwned>(json: Option<String>) -> Vec<T> {
+    json.and_then(|json| serde_json::from_str::<Vec<serde_json::Value>>(&json).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect()
+}
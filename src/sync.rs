@@ -0,0 +1,252 @@
+use wasm_bindgen::prelude::*;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(page: usize, x: f64, y: f64, zoom: f64) -> ViewState {
+        ViewState { page, x, y, zoom }
+    }
+
+    #[test]
+    fn absolute_test() {
+        let mut group = Group::new(SyncMode::Absolute);
+        group.join(1, 10, state(0, 0.0, 0.0, 1.0));
+        group.join(2, 5, state(2, 10.0, 0.0, 1.0));
+        assert_eq!(group.update(1, state(3, 0.0, 0.0, 1.0)), vec![(2, state(3, 0.0, 0.0, 1.0))]);
+        // 頁数を超えたら最後の頁
+        assert_eq!(group.update(1, state(8, 5.0, 5.0, 2.0)), vec![(2, state(4, 5.0, 5.0, 2.0))]);
+        // 同じ状態になっていれば送り返さない
+        assert_eq!(group.update(2, state(4, 5.0, 5.0, 2.0)), vec![]);
+    }
+
+    #[test]
+    fn relative_test() {
+        let mut group = Group::new(SyncMode::Relative);
+        group.join(1, 10, state(0, 0.0, 0.0, 1.0));
+        group.join(2, 10, state(2, 10.0, 0.0, 2.0));
+        // 頁と位置の差を保つ(移動量は倍率の比をかける)
+        assert_eq!(group.update(1, state(1, 5.0, -5.0, 1.5)), vec![(2, state(3, 20.0, -10.0, 3.0))]);
+        assert_eq!(group.update(2, state(2, 30.0, -10.0, 3.0)), vec![(1, state(0, 10.0, -5.0, 1.5))]);
+        assert_eq!(group.update(1, state(0, 10.0, -5.0, 1.5)), vec![]);
+        // 先頭より前には戻らない
+        group.update(2, state(0, 30.0, -10.0, 3.0));
+        assert_eq!(group.members.iter().find(|m| m.id == 1).unwrap().state.page, 0);
+        // 戻ったときは参加したときの差のまま
+        assert_eq!(group.update(2, state(3, 30.0, -10.0, 3.0)), vec![(1, state(1, 10.0, -5.0, 1.5))]);
+    }
+
+    #[test]
+    fn relative_clamp_test() {
+        let mut group = Group::new(SyncMode::Relative);
+        group.join(1, 10, state(2, 0.0, 0.0, 1.0));
+        group.join(2, 10, state(0, 0.0, 0.0, 1.0));
+        // 差は-2頁。先頭で止まっても差は変わらない
+        assert_eq!(group.update(1, state(0, 0.0, 0.0, 1.0)), vec![]);
+        assert_eq!(group.update(1, state(1, 0.0, 0.0, 1.0)), vec![]);
+        assert_eq!(group.update(1, state(3, 0.0, 0.0, 1.0)), vec![(2, state(1, 0.0, 0.0, 1.0))]);
+
+        // 後から参加したときは、その時点の差にする
+        group.join(3, 10, state(5, 0.0, 0.0, 1.0));
+        assert_eq!(group.update(1, state(4, 0.0, 0.0, 1.0)), vec![(2, state(2, 0.0, 0.0, 1.0)), (3, state(6, 0.0, 0.0, 1.0))]);
+
+        // 同期の方法を切り替えたときも、その時点の差にする
+        group.set_mode(SyncMode::Absolute);
+        group.update(1, state(0, 0.0, 0.0, 1.0));
+        group.set_mode(SyncMode::Relative);
+        group.update(2, state(4, 0.0, 0.0, 1.0));
+        assert_eq!(group.members.iter().find(|m| m.id == 1).unwrap().state.page, 4);
+    }
+
+    #[test]
+    fn leave_test() {
+        let mut group = Group::new(SyncMode::Absolute);
+        group.join(1, 10, state(0, 0.0, 0.0, 1.0));
+        group.join(2, 10, state(0, 0.0, 0.0, 1.0));
+        assert!(group.leave(2));
+        assert!(!group.leave(2));
+        assert_eq!(group.update(1, state(5, 0.0, 0.0, 1.0)), vec![]);
+        // 参加していなければ何もしない
+        assert_eq!(group.update(3, state(5, 0.0, 0.0, 1.0)), vec![]);
+    }
+}
+
+/// 同期の方法
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncMode {
+    /// 同じ頁、同じ位置と倍率にする
+    Absolute,
+    /// 参加したときの頁、位置と倍率の差を保つ
+    Relative,
+}
+
+/// 同期するビューアの表示状態
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ViewState {
+    pub page: usize,
+    pub x: f64,
+    pub y: f64,
+    pub zoom: f64,
+}
+
+struct Member {
+    id: u32,
+    /// 画像の数
+    len: usize,
+    state: ViewState,
+    /// 差を測る基準の状態(参加したとき、同期の方法を変えたときの状態)
+    origin: ViewState,
+}
+
+/// 表示状態を揃えるビューアの組
+pub struct Group {
+    mode: SyncMode,
+    members: Vec<Member>,
+}
+
+impl Group {
+    pub fn new(mode: SyncMode) -> Self {
+        Self { mode, members: Vec::new() }
+    }
+
+    /// 参加する(参加済みなら状態を置き換える)
+    /// 相対的な同期の差は参加した時点のものにする
+    pub fn join(&mut self, id: u32, len: usize, state: ViewState) {
+        match self.members.iter_mut().find(|member| member.id == id) {
+            Some(member) => {
+                member.len = len;
+                member.state = state;
+            }
+            None => self.members.push(Member { id, len, state, origin: state }),
+        }
+        self.reset_origins();
+    }
+
+    pub fn set_mode(&mut self, mode: SyncMode) {
+        self.mode = mode;
+        self.reset_origins();
+    }
+
+    /// 今の状態を差の基準にする
+    fn reset_origins(&mut self) {
+        for member in &mut self.members {
+            member.origin = member.state;
+        }
+    }
+
+    pub fn leave(&mut self, id: u32) -> bool {
+        let len = self.members.len();
+        self.members.retain(|member| member.id != id);
+        self.members.len() != len
+    }
+
+    /// idのビューアの状態が変わったときに、他のビューアが取るべき状態
+    /// 既にその状態のビューアは含めない(送り返しで循環しないように)
+    pub fn update(&mut self, id: u32, state: ViewState) -> Vec<(u32, ViewState)> {
+        let (old, origin) = match self.members.iter_mut().find(|member| member.id == id) {
+            Some(member) => (std::mem::replace(&mut member.state, state), member.origin),
+            None => return Vec::new(),
+        };
+        // 送った状態がそのまま返ってきたときは何もしない
+        if old == state {
+            return Vec::new();
+        }
+        let mode = self.mode;
+        let mut changes = Vec::new();
+        for member in self.members.iter_mut().filter(|member| member.id != id) {
+            let last = member.len.saturating_sub(1);
+            let next = match mode {
+                SyncMode::Absolute => ViewState { page: state.page.min(last), ..state },
+                // 基準の状態からの変化を、それぞれの基準の状態に加える
+                // (頁が端で止まっても差は失われない)
+                SyncMode::Relative => {
+                    let page = member.origin.page as isize + state.page as isize - origin.page as isize;
+                    let ratio = if origin.zoom > 0.0 { member.origin.zoom / origin.zoom } else { 1.0 };
+                    ViewState {
+                        page: (page.max(0) as usize).min(last),
+                        x: member.origin.x + (state.x - origin.x) * ratio,
+                        y: member.origin.y + (state.y - origin.y) * ratio,
+                        zoom: state.zoom * ratio,
+                    }
+                }
+            };
+            if next != member.state {
+                member.state = next;
+                changes.push((member.id, next));
+            }
+        }
+        changes
+    }
+}
+
+/// 他のビューアに送る状態
+#[derive(Serialize)]
+struct Change {
+    id: u32,
+    #[serde(flatten)]
+    state: ViewState,
+}
+
+/// 頁送り、移動、拡大縮小を揃えるビューアの組
+#[wasm_bindgen]
+pub struct SyncGroup {
+    group: Group,
+}
+
+#[wasm_bindgen]
+impl SyncGroup {
+    #[wasm_bindgen(constructor)]
+    pub fn new(mode: SyncMode) -> Self {
+        Self { group: Group::new(mode) }
+    }
+
+    pub fn mode(&self) -> SyncMode {
+        self.group.mode
+    }
+
+    pub fn set_mode(&mut self, mode: SyncMode) {
+        self.group.set_mode(mode);
+    }
+
+    /// 画像の数と今の表示状態で参加する
+    pub fn join(&mut self, id: u32, len: usize, page: usize, x: f64, y: f64, zoom: f64) {
+        self.group.join(id, len, ViewState { page, x, y, zoom });
+    }
+
+    pub fn leave(&mut self, id: u32) -> bool {
+        self.group.leave(id)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.group.members.iter().any(|member| member.id == id)
+    }
+
+    /// 頁を移動したことを知らせ、他のビューアが取るべき状態を返す(JSON配列)
+    /// `[{"id": 2, "page": 3, "x": 0.0, "y": 0.0, "zoom": 1.0}, ...]`
+    pub fn page(&mut self, id: u32, page: usize) -> String {
+        let state = match self.group.members.iter().find(|member| member.id == id) {
+            Some(member) => ViewState { page, ..member.state },
+            None => return "[]".to_string(),
+        };
+        self.update(id, state)
+    }
+
+    /// 画像を移動、拡大縮小したことを知らせ、他のビューアが取るべき状態を返す(JSON配列)
+    pub fn transform(&mut self, id: u32, x: f64, y: f64, zoom: f64) -> String {
+        let state = match self.group.members.iter().find(|member| member.id == id) {
+            Some(member) => ViewState { x, y, zoom, ..member.state },
+            None => return "[]".to_string(),
+        };
+        self.update(id, state)
+    }
+}
+
+impl SyncGroup {
+    fn update(&mut self, id: u32, state: ViewState) -> String {
+        let changes: Vec<Change> = self.group.update(id, state).into_iter()
+            .map(|(id, state)| Change { id, state })
+            .collect();
+        serde_json::to_string(&changes).unwrap_or_else(|_| "[]".to_string())
+    }
+}
@@ -1,125 +1,1132 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::rc::Rc;
+
 use wasm_bindgen::prelude::*;
-use web_sys::{Element, ElementCreationOptions, Node};
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Element, ElementCreationOptions, HtmlCanvasElement, HtmlElement, HtmlImageElement, Node};
 
 use crate::viewer::ViewerImage;
 
+#[cfg(test)]
+mod test {
+    use super::virtual_scroll::{visible_range, reveal};
+    use super::escape;
+    use super::metadata_view::{badge, render_manifest};
+    use super::search_view::page_numbers;
+    use super::filmstrip_view::scrubber_position;
+    use crate::iiif_manifest::Manifest;
+
+    #[test]
+    fn visible_range_test() {
+        // 1列, 高さ45px, 450pxの表示領域
+        assert_eq!(visible_range(1000, 1, 45.0, 0.0, 450.0, 5), 0..15);
+        assert_eq!(visible_range(1000, 1, 45.0, 4500.0, 450.0, 5), 95..115);
+        assert_eq!(visible_range(1000, 1, 45.0, 44950.0, 450.0, 5), 993..1000);
+        // 4列, 高さ200px
+        assert_eq!(visible_range(1000, 4, 200.0, 1000.0, 600.0, 1), 16..36);
+        assert_eq!(visible_range(10, 4, 200.0, 0.0, 600.0, 1), 0..10);
+        assert_eq!(visible_range(0, 1, 45.0, 0.0, 450.0, 5), 0..0);
+    }
+
+    #[test]
+    fn reveal_test() {
+        // 見えていれば動かさない
+        assert_eq!(reveal(5, 1, 45.0, 0.0, 450.0), None);
+        // 上に隠れていれば上端に、下に隠れていれば下端に合わせる
+        assert_eq!(reveal(5, 1, 45.0, 900.0, 450.0), Some(225.0));
+        assert_eq!(reveal(20, 1, 45.0, 0.0, 450.0), Some(495.0));
+        assert_eq!(reveal(9, 4, 200.0, 0.0, 600.0), None);
+        assert_eq!(reveal(13, 4, 200.0, 0.0, 600.0), Some(200.0));
+    }
+
+    #[test]
+    fn scrubber_position_test() {
+        assert_eq!(scrubber_position(0, 11, false), 0.0);
+        assert_eq!(scrubber_position(5, 11, false), 0.5);
+        assert_eq!(scrubber_position(10, 11, false), 1.0);
+        // 右から左なら先頭は右端
+        assert_eq!(scrubber_position(0, 11, true), 1.0);
+        assert_eq!(scrubber_position(10, 11, true), 0.0);
+        assert_eq!(scrubber_position(0, 1, false), 0.0);
+    }
+
+    #[test]
+    fn badge_test() {
+        let cc = badge("https://creativecommons.org/licenses/by-sa/4.0/").unwrap();
+        assert_eq!(cc.name, "CC BY-SA 4.0");
+        assert_eq!(cc.class, "creative-commons");
+        assert_eq!(badge("http://creativecommons.org/publicdomain/zero/1.0/").unwrap().name, "CC0 1.0");
+        let rs = badge("http://rightsstatements.org/vocab/NoC-OKLR/1.0/").unwrap();
+        assert_eq!(rs.name, "No Copyright - Other Known Legal Restrictions");
+        assert_eq!(rs.class, "no-copyright");
+        assert!(badge("http://rightsstatements.org/vocab/Unknown/1.0/").is_none());
+        assert!(badge("https://example.org/license").is_none());
+    }
+
+    #[test]
+    fn render_manifest_test() {
+        let manifest = serde_json::from_str::<Manifest>(include_str!("../test/hokusai.json")).unwrap();
+        let html = render_manifest(&manifest);
+        assert!(html.contains("<img class=\"metadata-logo\" src=\"https://style.europeana.eu/images/europeana-logo-default.png\""));
+        assert!(html.contains("<span class=\"badge no-copyright\">No Copyright - Other Known Legal Restrictions</span>"));
+        assert!(html.contains("<h6>See also</h6>"));
+        assert!(html.contains(">application/ld+json</a>"));
+        assert_eq!(escape("<b>\"a\" & 'b'</b>"), "&lt;b&gt;&quot;a&quot; &amp; &#39;b&#39;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn page_numbers_test() {
+        // 5ページずつ、表示中のページを中央に
+        assert_eq!(page_numbers(1, 20, 5), 1..6);
+        assert_eq!(page_numbers(10, 20, 5), 8..13);
+        assert_eq!(page_numbers(19, 20, 5), 16..21);
+        assert_eq!(page_numbers(2, 3, 5), 1..4);
+        assert_eq!(page_numbers(1, 0, 5), 1..1);
+    }
+}
 
 pub trait View {
     fn new(element: Element) -> Self;
-    fn initialize(&self, viewer_images: &Vec<ViewerImage>);
+    /// 画像を表示する(前の画像は消す)
+    fn initialize(&mut self, viewer_images: &Vec<ViewerImage>);
+    /// 空にする
+    fn reset(&mut self);
+    /// 表示中の画像(見開きなら2枚)を強調し、見える位置までスクロールする
+    fn set_current(&mut self, current: Range<usize>);
+}
+
+/// 項目がクリックされたときにviewの要素から送るイベント(detailは画像の番号)
+pub const SELECT_EVENT: &str = "image-select";
+
+/// HTMLとして安全な文字列にする
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// 見えている範囲の項目だけを作って表示する
+/// 範囲外の行は上下のpaddingで高さだけを確保する
+pub mod virtual_scroll {
+    use super::*;
+
+    /// 表示する項目
+    pub struct Item {
+        /// Viewer内の画像の番号
+        pub index: usize,
+        pub src: String,
+        pub label: String,
+        pub thumbnail: Option<String>,
+    }
+
+    /// 項目の大きさ
+    #[derive(Clone, Copy)]
+    pub struct Layout {
+        /// 1項目の幅(marginを含む), 0なら1列で表示し、高さは実際の要素から測る
+        pub item_width: f64,
+        /// 1行の高さ(marginを含む)
+        pub item_height: f64,
+        /// 見えている範囲の前後に余分に作る行数
+        pub overscan: usize,
+        /// 横1行に並べる(item_widthずつ横にスクロールする)
+        pub horizontal: bool,
+    }
+
+    /// 項目の要素を作る
+    pub type Make = fn(&Item) -> Option<Element>;
+
+    /// 表示する項目の範囲
+    pub fn visible_range(len: usize, columns: usize, row_height: f64, scroll_top: f64, height: f64, overscan: usize) -> Range<usize> {
+        if len == 0 || row_height <= 0.0 {
+            return 0..0;
+        }
+        let columns = columns.max(1);
+        let rows = (len + columns - 1) / columns;
+        let first = ((scroll_top / row_height).floor() as usize).saturating_sub(overscan).min(rows);
+        let last = (((scroll_top + height) / row_height).ceil() as usize + overscan).min(rows);
+        (first * columns)..(last * columns).min(len)
+    }
+
+    /// position番目の項目が見えるようにするスクロール位置
+    /// 既に見えていればNone
+    pub fn reveal(position: usize, columns: usize, row_height: f64, scroll_top: f64, height: f64) -> Option<f64> {
+        let top = (position / columns.max(1)) as f64 * row_height;
+        if top < scroll_top {
+            Some(top)
+        } else if top + row_height > scroll_top + height {
+            Some((top + row_height - height).max(0.0))
+        } else {
+            None
+        }
+    }
+
+    struct State {
+        element: Element,
+        items: Vec<Item>,
+        layout: Layout,
+        make: Make,
+        /// 作った要素(項目の番号 -> 要素)
+        rendered: BTreeMap<usize, Element>,
+        range: Range<usize>,
+        /// 表示中の画像の番号
+        current: Range<usize>,
+    }
+
+    pub struct VirtualScroll {
+        state: Rc<RefCell<State>>,
+        onscroll: Option<Closure<dyn FnMut()>>,
+        onclick: Option<Closure<dyn FnMut(web_sys::Event)>>,
+    }
+
+    impl VirtualScroll {
+        pub fn new(element: Element, layout: Layout, make: Make) -> Self {
+            let state = State { element, items: Vec::new(), layout, make, rendered: BTreeMap::new(), range: 0..0, current: 0..0 };
+            Self { state: Rc::new(RefCell::new(state)), onscroll: None, onclick: None }
+        }
+
+        /// 作った要素と項目をすべて消す
+        pub fn reset(&mut self) {
+            let mut state = self.state.borrow_mut();
+            for (_, element) in std::mem::replace(&mut state.rendered, BTreeMap::new()) {
+                element.remove();
+            }
+            state.items.clear();
+            state.range = 0..0;
+            state.current = 0..0;
+            if let Some(element) = state.element.dyn_ref::<HtmlElement>() {
+                let style = element.style();
+                for property in &["padding-top", "padding-bottom", "padding-left", "padding-right"] {
+                    let _ = style.remove_property(property);
+                }
+            }
+            state.element.set_scroll_top(0);
+            state.element.set_scroll_left(0);
+        }
+
+        /// 項目を置き換えて先頭から表示する
+        pub fn set_items(&mut self, items: Vec<Item>) {
+            self.reset();
+            self.state.borrow_mut().items = items;
+            if self.onscroll.is_none() {
+                let state = self.state.clone();
+                let onscroll = Closure::wrap(Box::new(move || {
+                    state.borrow_mut().render();
+                }) as Box<dyn FnMut()>);
+                self.state.borrow().element
+                    .add_event_listener_with_callback("scroll", onscroll.as_ref().unchecked_ref())
+                    .unwrap_or_else(|_| crate::viewer::log("Cannot listen to scroll"));
+                self.onscroll = Some(onscroll);
+            }
+            if self.onclick.is_none() {
+                let element = self.state.borrow().element.clone();
+                let onclick = Closure::wrap(Box::new(move |event: web_sys::Event| {
+                    if let Some(index) = clicked_index(&event) {
+                        select(&element, index);
+                    }
+                }) as Box<dyn FnMut(web_sys::Event)>);
+                self.state.borrow().element
+                    .add_event_listener_with_callback("click", onclick.as_ref().unchecked_ref())
+                    .unwrap_or_else(|_| crate::viewer::log("Cannot listen to click"));
+                self.onclick = Some(onclick);
+            }
+            self.state.borrow_mut().render();
+        }
+
+        /// 表示中の画像を強調し、見える位置までスクロールする
+        pub fn set_current(&self, current: Range<usize>) {
+            let mut state = self.state.borrow_mut();
+            state.current = current;
+            for (&i, element) in &state.rendered {
+                let active = state.current.contains(&state.items[i].index);
+                let _ = element.class_list().toggle_with_force("active", active);
+            }
+            state.reveal();
+        }
+
+        /// 表示中の画像が見える位置までスクロールして作り直す
+        pub fn reveal(&self) {
+            self.state.borrow_mut().reveal();
+        }
+    }
+
+    /// クリックされた項目の画像の番号
+    fn clicked_index(event: &web_sys::Event) -> Option<usize> {
+        let target = event.target()?.dyn_into::<Element>().ok()?;
+        let item = target.closest("[data-index]").ok()??;
+        item.get_attribute("data-index")?.parse().ok()
+    }
+
+    /// 選択されたことをviewの要素から知らせる
+    pub fn select(element: &Element, index: usize) {
+        let mut init = web_sys::CustomEventInit::new();
+        init.bubbles(true);
+        init.detail(&JsValue::from(index as u32));
+        match web_sys::CustomEvent::new_with_event_init_dict(SELECT_EVENT, &init) {
+            Ok(event) => {
+                let _ = element.dispatch_event(&event);
+            }
+            Err(_) => crate::viewer::log("Cannot create select event"),
+        }
+    }
+
+    impl State {
+        /// 表示領域の大きさ
+        /// 隠れているときはwindowの大きさで見積もる
+        fn viewport(&self) -> (f64, f64) {
+            let (width, height) = (self.element.client_width(), self.element.client_height());
+            if width > 0 && height > 0 {
+                return (width as f64, height as f64);
+            }
+            let window = web_sys::window().expect("no global `window` exists");
+            let size = |v: Result<JsValue, JsValue>| v.ok().and_then(|v| v.as_f64()).unwrap_or(0.0);
+            (size(window.inner_width()), size(window.inner_height()))
+        }
+
+        fn columns(&self, width: f64) -> usize {
+            if self.layout.horizontal {
+                1
+            } else if self.layout.item_width > 0.0 {
+                ((width / self.layout.item_width).floor() as usize).max(1)
+            } else { 1 }
+        }
+
+        /// スクロールする方向の(1行の大きさ, スクロール位置, 表示領域の大きさ)
+        fn axis(&self, width: f64, height: f64) -> (f64, f64, f64) {
+            if self.layout.horizontal {
+                (self.layout.item_width, self.element.scroll_left() as f64, width)
+            } else {
+                (self.layout.item_height, self.element.scroll_top() as f64, height)
+            }
+        }
+
+        fn reveal(&mut self) {
+            if let Some(position) = self.items.iter().position(|item| self.current.contains(&item.index)) {
+                let (width, height) = self.viewport();
+                let columns = self.columns(width);
+                let (step, scroll, size) = self.axis(width, height);
+                if let Some(position) = reveal(position, columns, step, scroll, size) {
+                    if self.layout.horizontal {
+                        self.element.set_scroll_left(position as i32);
+                    } else {
+                        self.element.set_scroll_top(position as i32);
+                    }
+                }
+            }
+            self.render();
+        }
+
+        fn render(&mut self) {
+            let (width, height) = self.viewport();
+            let columns = self.columns(width);
+            let (step, scroll, size) = self.axis(width, height);
+            let range = visible_range(self.items.len(), columns, step, scroll, size, self.layout.overscan);
+            if range == self.range {
+                return;
+            }
+
+            // 範囲外の要素を消す
+            let outside: Vec<usize> = self.rendered.keys().filter(|i| !range.contains(i)).cloned().collect();
+            for i in outside {
+                if let Some(element) = self.rendered.remove(&i) {
+                    element.remove();
+                }
+            }
+            // 範囲内の要素を順番通りに作る
+            for i in range.clone() {
+                if self.rendered.contains_key(&i) {
+                    continue;
+                }
+                let element = match (self.make)(&self.items[i]) {
+                    Some(element) => element,
+                    None => continue,
+                };
+                if self.current.contains(&self.items[i].index) {
+                    let _ = element.class_list().add_1("active");
+                }
+                let next = self.rendered.range(i + 1..).next().map(|(_, e)| Node::from(e.clone()));
+                if self.element.insert_before(&element, next.as_ref()).is_ok() {
+                    self.rendered.insert(i, element);
+                }
+            }
+
+            // 範囲外の行の高さ
+            let rows = (self.items.len() + columns - 1) / columns;
+            let before = range.start / columns;
+            let after = rows - (range.end + columns - 1) / columns;
+            let (start, end) = if self.layout.horizontal { ("padding-left", "padding-right") } else { ("padding-top", "padding-bottom") };
+            if let Some(element) = self.element.dyn_ref::<HtmlElement>() {
+                let style = element.style();
+                let _ = style.set_property(start, &format!("{}px", before as f64 * step));
+                let _ = style.set_property(end, &format!("{}px", after as f64 * step));
+            }
+            self.range = range;
+
+            // 縦1列なら実際の行の高さで測り直す
+            if !self.layout.horizontal && self.layout.item_width <= 0.0 {
+                let measured = self.rendered.values().next()
+                    .map(|e| e.get_bounding_client_rect().height())
+                    .unwrap_or(0.0);
+                if measured > 0.0 && (measured - self.layout.item_height).abs() > 0.5 {
+                    self.layout.item_height = measured;
+                    self.range = 0..0;
+                    self.render();
+                }
+            }
+        }
+    }
 }
 
 pub mod list_view {
     use super::*;
+    use super::virtual_scroll::{Item, Layout, VirtualScroll};
 
     /// Manifestに含まれる画像のリスト
     pub struct ListView {
-        element: Element
+        items: VirtualScroll,
+    }
+
+    impl ListView {
+        /// 表示中の画像までスクロールして作り直す
+        pub fn reveal(&self) {
+            self.items.reveal();
+        }
+
+        fn make(item: &Item) -> Option<Element> {
+            let window = web_sys::window().expect("no global `window` exists");
+            let document = window.document().expect("should have a document on window");
+            let li = document.create_element_with_element_creation_options("li", ElementCreationOptions::new().is("image-list-item")).ok()?;
+            // liの詳細設定: srcを設定
+            li.set_attribute("src", &item.src).ok()?;
+            li.set_attribute("data-index", &item.index.to_string()).ok()?;
+            // liの詳細設定: labelを文字列として設定
+            li.set_text_content(Some(&item.label));
+            Some(li)
+        }
     }
 
     impl View for ListView {
         fn new(element: Element) -> Self {
-            Self { element }
-        }
-
-        fn initialize(&self, viewer_images: &Vec<ViewerImage>) {
-            for image in viewer_images {
-                // srcを取得
-                let src = &image.src;
-                // labelを取得
-                let label = &image.label;
-                // liをdocumentに追加
-                let window = web_sys::window().expect("no global `window` exists");
-                let document = window.document().expect("should have a document on window");
-                let li = match document.create_element_with_element_creation_options("li", ElementCreationOptions::new().is("image-list-item")) {
-                    Ok(e) => e,
-                    Err(_) => { continue; }
-                };
-                // liの詳細設定: srcを設定
-                li.set_attribute("src", src);
-                // liの詳細設定: inner_htmlを設定
-                li.set_inner_html(label);
-                // set!
-                self.element.append_child(&Node::from(li));
-            }
+            let layout = Layout { item_width: 0.0, item_height: 45.0, overscan: 10, horizontal: false };
+            Self { items: VirtualScroll::new(element, layout, Self::make) }
+        }
+
+        fn initialize(&mut self, viewer_images: &Vec<ViewerImage>) {
+            let items = viewer_images.iter().enumerate()
+                .map(|(index, image)| Item { index, src: image.src.clone(), label: image.label.clone(), thumbnail: None })
+                .collect();
+            self.items.set_items(items);
+        }
+
+        fn reset(&mut self) {
+            self.items.reset();
+        }
+
+        fn set_current(&mut self, current: Range<usize>) {
+            self.items.set_current(current);
         }
     }
 }
 
 pub mod icon_view {
     use super::*;
+    use super::virtual_scroll::{Item, Layout, VirtualScroll};
+    use crate::iiif_manifest::{THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT};
+
+    /// icon-view-itemの大きさ(marginを含む), viewer.scssと合わせる
+    const ICON_WIDTH: f64 = 160.0;
+    const ICON_HEIGHT: f64 = 200.0;
+
+    thread_local! {
+        /// 縮小したthumbnail(画像のURLごとのdata URL)
+        /// 見えなくなった項目を作り直すときやfilmstripで使い回す
+        static THUMBNAILS: RefCell<BTreeMap<String, String>> = RefCell::new(BTreeMap::new());
+    }
 
     /// Manifestに含まれる画像の一覧
     pub struct IconView {
         element: Element,
+        items: VirtualScroll,
+    }
+
+    impl IconView {
+        /// 表示中の画像までスクロールして作り直す
+        pub fn reveal(&self) {
+            self.items.reveal();
+        }
+
+        /// 見えるようになった項目だけthumbnailを読み込む
+        fn make(item: &Item) -> Option<Element> {
+            let window = web_sys::window().expect("no global `window` exists");
+            let document = window.document().expect("should have a document on window");
+            let icon_view_item = document.create_element("icon-view-item").ok()?;
+            // itemの詳細設定: srcを設定
+            icon_view_item.set_attribute("src", &item.src).ok()?;
+            icon_view_item.set_attribute("data-index", &item.index.to_string()).ok()?;
+            // itemの詳細設定: labelを設定
+            icon_view_item.set_attribute("label", &item.label).ok()?;
+            // set! thumbnail
+            let thumbnail = HtmlImageElement::new().ok()?;
+            match &item.thumbnail {
+                Some(src) => thumbnail.set_src(src),
+                None => Self::downscale(&thumbnail, &item.src),
+            }
+            icon_view_item.append_child(&Node::from(thumbnail)).ok()?;
+            Some(icon_view_item)
+        }
+
+        /// 画像を縮小してthumbnailにする
+        /// 別オリジンで読み出せなければ画像をそのまま使う
+        pub fn downscale(thumbnail: &HtmlImageElement, src: &str) {
+            if let Some(url) = THUMBNAILS.with(|thumbnails| thumbnails.borrow().get(src).cloned()) {
+                return thumbnail.set_src(&url);
+            }
+            let source = match HtmlImageElement::new() {
+                Ok(source) => source,
+                Err(_) => return,
+            };
+            source.set_cross_origin(Some("Anonymous"));
+
+            let loaded = source.clone();
+            let image = thumbnail.clone();
+            let original = src.to_string();
+            // loadとerrorのどちらか一方だけが呼ばれ、呼ばれた後に解放される
+            let onloadend = Closure::once_into_js(move || {
+                loaded.set_onload(None);
+                loaded.set_onerror(None);
+                if loaded.natural_width() == 0 || loaded.natural_height() == 0 {
+                    return image.set_src(&original);
+                }
+                let scale = (THUMBNAIL_WIDTH as f64 / loaded.natural_width() as f64)
+                    .min(THUMBNAIL_HEIGHT as f64 / loaded.natural_height() as f64)
+                    .min(1.0);
+                let (width, height) = (loaded.natural_width() as f64 * scale, loaded.natural_height() as f64 * scale);
+                let document = web_sys::window().unwrap().document().unwrap();
+                let canvas = match document.create_element("canvas")
+                    .map(|e| e.dyn_into::<HtmlCanvasElement>()) {
+                    Ok(Ok(canvas)) => canvas,
+                    _ => return image.set_src(&original),
+                };
+                canvas.set_width(width as u32);
+                canvas.set_height(height as u32);
+                let context = match canvas.get_context("2d")
+                    .map(|c| c.map(|c| c.dyn_into::<CanvasRenderingContext2d>())) {
+                    Ok(Some(Ok(context))) => context,
+                    _ => return image.set_src(&original),
+                };
+                let _ = context.draw_image_with_html_image_element_and_dw_and_dh(&loaded, 0.0, 0.0, width, height);
+                match canvas.to_data_url() {
+                    Ok(url) => {
+                        image.set_src(&url);
+                        THUMBNAILS.with(|thumbnails| thumbnails.borrow_mut().insert(original, url));
+                    }
+                    Err(_) => image.set_src(&original),
+                }
+            });
+            source.set_onload(Some(onloadend.unchecked_ref()));
+            source.set_onerror(Some(onloadend.unchecked_ref()));
+            source.set_src(src);
+        }
     }
 
     impl View for IconView {
         fn new(element: Element) -> Self {
-            Self { element }
+            let layout = Layout { item_width: ICON_WIDTH, item_height: ICON_HEIGHT, overscan: 2, horizontal: false };
+            Self { element: element.clone(), items: VirtualScroll::new(element, layout, Self::make) }
         }
 
-        fn initialize(&self, viewer_images: &Vec<ViewerImage>) {
+        fn initialize(&mut self, viewer_images: &Vec<ViewerImage>) {
             self.element.class_list().add_1("row");
 
-            for image in viewer_images {
-                // srcを取得
-                let src = &image.src;
-                // labelを取得
-                let label = &image.label;
-                // thumbnailを取得
-                match &image.thumbnail {
-                    Some(thumbnail) => {
-                        let thumbnail = thumbnail.clone();
-                        let window = web_sys::window().expect("no global `window` exists");
-                        let document = window.document().expect("should have a document on window");
-                        let icon_view_item = match document.create_element("icon-view-item") {
-                            Ok(e) => e,
-                            Err(_) => continue,
-                        };
-                        // itemの詳細設定: srcを設定
-                        icon_view_item.set_attribute("src", src);
-                        // itemの詳細設定: labelを設定
-                        icon_view_item.set_attribute("label", label);
-                        // set! thumbnail
-                        icon_view_item.append_child(&Node::from(thumbnail));
-                        // set!
-                        self.element.append_child(&Node::from(icon_view_item));
-                    }
-                    None => continue,
+            let items = viewer_images.iter().enumerate()
+                .map(|(index, image)| Item { index, src: image.src.clone(), label: image.label.clone(), thumbnail: image.thumbnail.clone() })
+                .collect();
+            self.items.set_items(items);
+        }
+
+        fn reset(&mut self) {
+            self.items.reset();
+        }
+
+        fn set_current(&mut self, current: Range<usize>) {
+            self.items.set_current(current);
+        }
+    }
+}
+
+pub mod filmstrip_view {
+    use super::*;
+    use super::icon_view::IconView;
+    use super::virtual_scroll::{select, Item, Layout, VirtualScroll};
+    use web_sys::HtmlInputElement;
+
+    /// filmstrip-itemの大きさ(marginを含む), viewer.scssと合わせる
+    const FILM_WIDTH: f64 = 72.0;
+    const FILM_HEIGHT: f64 = 96.0;
+
+    /// scrubberの値(画像の番号)の位置(左端0.0から右端1.0)
+    /// 右から左に読むときは先頭が右端になる
+    pub fn scrubber_position(index: usize, len: usize, right_to_left: bool) -> f64 {
+        let position = if len > 1 { index.min(len - 1) as f64 / (len - 1) as f64 } else { 0.0 };
+        if right_to_left { 1.0 - position } else { position }
+    }
+
+    /// scrubberで選んでいる画像のpreviewに使う情報
+    struct Page {
+        src: String,
+        label: String,
+        thumbnail: Option<String>,
+    }
+
+    struct Scrubber {
+        input: HtmlInputElement,
+        preview: Element,
+        pages: Vec<Page>,
+        right_to_left: bool,
+    }
+
+    impl Scrubber {
+        fn index(&self) -> Option<usize> {
+            let value = self.input.value().parse::<usize>().ok()?;
+            if value >= 1 && value <= self.pages.len() { Some(value - 1) } else { None }
+        }
+
+        /// 選んでいる画像のthumbnailとページ番号をscrubberの上に出す
+        fn show_preview(&self) {
+            let index = match self.index() {
+                Some(index) => index,
+                None => return,
+            };
+            let page = &self.pages[index];
+            let position = scrubber_position(index, self.pages.len(), self.right_to_left);
+            // scrubberの左右のpadding(16px)を除いた幅の中で位置を合わせる
+            let _ = self.preview.set_attribute("style", &format!("left: calc(16px + (100% - 32px) * {});", position));
+            self.preview.set_inner_html(&format!("<span>{} / {} {}</span>", index + 1, self.pages.len(), escape(&page.label)));
+            if let Ok(image) = HtmlImageElement::new() {
+                match &page.thumbnail {
+                    Some(src) => image.set_src(src),
+                    None => image.set_src(&page.src),
+                }
+                let _ = self.preview.insert_adjacent_element("afterbegin", &image);
+            }
+            let _ = self.preview.class_list().remove_1("hide");
+        }
+    }
+
+    /// 画像の下に横1行で並べる画像の一覧と、ページを素早く移動するscrubber
+    pub struct FilmstripView {
+        element: Element,
+        items: VirtualScroll,
+        scrubber: Option<Rc<RefCell<Scrubber>>>,
+        oninput: Option<Closure<dyn FnMut()>>,
+        onchange: Option<Closure<dyn FnMut()>>,
+        /// 右から左に並べる
+        right_to_left: bool,
+    }
+
+    impl FilmstripView {
+        /// 表示中の画像までスクロールして作り直す
+        pub fn reveal(&self) {
+            self.items.reveal();
+        }
+
+        /// 右から左に並べるか否か(次のinitializeから)
+        pub fn set_right_to_left(&mut self, right_to_left: bool) {
+            self.right_to_left = right_to_left;
+        }
+
+        fn make(item: &Item) -> Option<Element> {
+            let document = web_sys::window()?.document()?;
+            let film = document.create_element("div").ok()?;
+            film.class_list().add_1("filmstrip-item").ok()?;
+            film.set_attribute("data-index", &item.index.to_string()).ok()?;
+            film.set_attribute("title", &item.label).ok()?;
+            let thumbnail = HtmlImageElement::new().ok()?;
+            match &item.thumbnail {
+                Some(src) => thumbnail.set_src(src),
+                None => IconView::downscale(&thumbnail, &item.src),
+            }
+            film.append_child(&Node::from(thumbnail)).ok()?;
+            Some(film)
+        }
+
+        /// scrubberを作り、動かしている間はpreviewを出し、離したら選択を知らせる
+        fn create_scrubber(&mut self) -> Option<()> {
+            let document = web_sys::window()?.document()?;
+            let wrapper = document.create_element("div").ok()?;
+            wrapper.class_list().add_1("filmstrip-scrubber").ok()?;
+            let input = document.create_element("input").ok()?.dyn_into::<HtmlInputElement>().ok()?;
+            input.set_type("range");
+            input.set_min("1");
+            let preview = document.create_element("div").ok()?;
+            preview.class_list().add_2("filmstrip-preview", "hide").ok()?;
+            wrapper.insert_adjacent_element("beforeend", &preview).ok()?;
+            wrapper.insert_adjacent_element("beforeend", &input).ok()?;
+            self.element.insert_adjacent_element("beforeend", &wrapper).ok()?;
+
+            let scrubber = Rc::new(RefCell::new(Scrubber { input: input.clone(), preview, pages: Vec::new(), right_to_left: false }));
+            let state = scrubber.clone();
+            let oninput = Closure::wrap(Box::new(move || {
+                state.borrow().show_preview();
+            }) as Box<dyn FnMut()>);
+            let state = scrubber.clone();
+            let element = self.element.clone();
+            let onchange = Closure::wrap(Box::new(move || {
+                let scrubber = state.borrow();
+                let _ = scrubber.preview.class_list().add_1("hide");
+                if let Some(index) = scrubber.index() {
+                    select(&element, index);
                 }
+            }) as Box<dyn FnMut()>);
+            input.add_event_listener_with_callback("input", oninput.as_ref().unchecked_ref()).ok()?;
+            input.add_event_listener_with_callback("change", onchange.as_ref().unchecked_ref()).ok()?;
+            self.oninput = Some(oninput);
+            self.onchange = Some(onchange);
+            self.scrubber = Some(scrubber);
+            Some(())
+        }
+    }
+
+    impl View for FilmstripView {
+        fn new(element: Element) -> Self {
+            let document = web_sys::window().unwrap().document().unwrap();
+            let strip = document.create_element("div").unwrap();
+            let _ = strip.class_list().add_1("filmstrip-items");
+            let _ = element.insert_adjacent_element("afterbegin", &strip);
+
+            let layout = Layout { item_width: FILM_WIDTH, item_height: FILM_HEIGHT, overscan: 5, horizontal: true };
+            let mut view = Self { element, items: VirtualScroll::new(strip, layout, Self::make), scrubber: None, oninput: None, onchange: None, right_to_left: false };
+            if view.create_scrubber().is_none() {
+                crate::viewer::log("Cannot create scrubber");
+            }
+            view
+        }
+
+        /// 右から左に読むときは末尾の画像を左端に置く
+        fn initialize(&mut self, viewer_images: &Vec<ViewerImage>) {
+            let mut items: Vec<Item> = viewer_images.iter().enumerate()
+                .map(|(index, image)| Item { index, src: image.src.clone(), label: image.label.clone(), thumbnail: image.thumbnail.clone() })
+                .collect();
+            if self.right_to_left {
+                items.reverse();
+            }
+            self.items.set_items(items);
+
+            if let Some(scrubber) = &self.scrubber {
+                let mut scrubber = scrubber.borrow_mut();
+                scrubber.pages = viewer_images.iter()
+                    .map(|image| Page { src: image.src.clone(), label: image.label.clone(), thumbnail: image.thumbnail.clone() })
+                    .collect();
+                scrubber.right_to_left = self.right_to_left;
+                scrubber.input.set_max(&viewer_images.len().max(1).to_string());
+                scrubber.input.set_value("1");
+                let _ = scrubber.input.set_attribute("dir", if self.right_to_left { "rtl" } else { "ltr" });
+            }
+        }
+
+        fn reset(&mut self) {
+            self.items.reset();
+            if let Some(scrubber) = &self.scrubber {
+                let mut scrubber = scrubber.borrow_mut();
+                scrubber.pages.clear();
+                let _ = scrubber.preview.class_list().add_1("hide");
+            }
+        }
+
+        fn set_current(&mut self, current: Range<usize>) {
+            if let Some(scrubber) = &self.scrubber {
+                scrubber.borrow().input.set_value(&(current.start + 1).to_string());
             }
+            self.items.set_current(current);
         }
     }
 }
 
-//pub mod search_view {
-//    use super::*;
-//
-//    /// Manifestの検索結果
-//    #[wasm_bindgen]
-//    pub struct SearchView {
-//        element: Element,
-//    }
-//
-//    impl View for SearchView {
-//        fn new(element: Element) -> Self {
-//            Self { element }
-//        }
-//
-//        /// 何もしない
-//        fn initialize(&self, viewer_images: &Vec<ViewerImage>) {
-////            unimplemented!()
-//        }
-//    }
-//
-//    #[wasm_bindgen]
-//    impl SearchView {
-//        pub fn results(&self, string: String) {
-//            // let results = match serde_json::from_str(&string) {
-//            //     Ok(s) => { s }
-//            //     Err(_) => { vec![] }
-//            // };
-//        }
-//    }
-//}
\ No newline at end of file
+pub mod metadata_view {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::iiif_manifest::{Canvas, Label, Links, Manifest, MetadataEntry};
+
+    /// 利用条件のバッジ
+    #[derive(Debug, PartialEq)]
+    pub struct Badge {
+        /// 表示する名前 (CC BY-SA 4.0など)
+        pub name: String,
+        /// 種類ごとのclass
+        pub class: &'static str,
+    }
+
+    /// RightsStatements.orgの権利表明
+    const RIGHTS_STATEMENTS: [(&str, &str, &str); 12] = [
+        ("InC", "In Copyright", "in-copyright"),
+        ("InC-OW-EU", "In Copyright - EU Orphan Work", "in-copyright"),
+        ("InC-EDU", "In Copyright - Educational Use Permitted", "in-copyright"),
+        ("InC-NC", "In Copyright - Non-Commercial Use Permitted", "in-copyright"),
+        ("InC-RUU", "In Copyright - Rights-holder(s) Unlocatable or Unidentifiable", "in-copyright"),
+        ("NoC-CR", "No Copyright - Contractual Restrictions", "no-copyright"),
+        ("NoC-NC", "No Copyright - Non-Commercial Use Only", "no-copyright"),
+        ("NoC-OKLR", "No Copyright - Other Known Legal Restrictions", "no-copyright"),
+        ("NoC-US", "No Copyright - United States", "no-copyright"),
+        ("CNE", "Copyright Not Evaluated", "other"),
+        ("UND", "Copyright Undetermined", "other"),
+        ("NKC", "No Known Copyright", "other"),
+    ];
+
+    /// Creative CommonsとRightsStatements.orgのURLならバッジを返す
+    pub fn badge(url: &str) -> Option<Badge> {
+        let path = url.trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/');
+        let mut parts = path.split('/');
+        match (parts.next()?, parts.next()?, parts.next()?, parts.next()?) {
+            ("creativecommons.org", "licenses", code, version) => Some(Badge {
+                name: format!("CC {} {}", code.to_uppercase(), version),
+                class: "creative-commons",
+            }),
+            ("creativecommons.org", "publicdomain", code @ "zero", version) | ("creativecommons.org", "publicdomain", code @ "mark", version) => Some(Badge {
+                name: format!("{} {}", if code == "zero" { "CC0" } else { "Public Domain Mark" }, version),
+                class: "creative-commons",
+            }),
+            ("rightsstatements.org", "vocab", code, _) | ("rightsstatements.org", "page", code, _) => {
+                let (_, name, class) = RIGHTS_STATEMENTS.iter().find(|(c, _, _)| *c == code)?;
+                Some(Badge { name: name.to_string(), class })
+            }
+            _ => None,
+        }
+    }
+
+    fn label(label: &Label) -> String {
+        escape(&label.texts().join(", "))
+    }
+
+    fn metadata(entries: &[MetadataEntry]) -> String {
+        if entries.is_empty() {
+            return String::new();
+        }
+        let rows: String = entries.iter()
+            .map(|entry| format!("<tr><th>{}</th><td>{}</td></tr>", label(&entry.label), label(&entry.value)))
+            .collect();
+        format!("<table class=\"metadata\">{}</table>", rows)
+    }
+
+    fn links(name: &str, links: Option<&Links>) -> String {
+        let items: String = links.iter().flat_map(|links| links.iter())
+            .map(|link| {
+                let text = link.label().or(link.format()).unwrap_or(link.id());
+                format!("<li><a href=\"{}\" target=\"_blank\" rel=\"noopener\">{}</a></li>", escape(link.id()), escape(text))
+            })
+            .collect();
+        if items.is_empty() {
+            return String::new();
+        }
+        format!("<h6>{}</h6><ul class=\"metadata-links\">{}</ul>", name, items)
+    }
+
+    /// Manifest全体の情報のHTML
+    pub fn render_manifest(manifest: &Manifest) -> String {
+        let mut html = String::new();
+        if let Some(logo) = manifest.logo().and_then(|logo| logo.iter().next()) {
+            html += &format!("<img class=\"metadata-logo\" src=\"{}\" alt=\"\">", escape(logo.id()));
+        }
+        html += &format!("<h5 class=\"metadata-label\">{}</h5>", label(&manifest.label));
+        if let Some(description) = manifest.description() {
+            html += &format!("<p class=\"metadata-description\">{}</p>", label(description));
+        }
+        html += &metadata(manifest.metadata());
+        if let Some(attribution) = manifest.attribution() {
+            html += &format!("<p class=\"metadata-attribution\">{}</p>", label(attribution));
+        }
+        if let Some(license) = manifest.license() {
+            // 外部の画像は使わず、名前をバッジにする
+            let content = match badge(license) {
+                Some(Badge { name, class }) =>
+                    format!("<span class=\"badge {}\">{}</span>", class, escape(&name)),
+                None => escape(license),
+            };
+            html += &format!("<p class=\"metadata-license\"><a href=\"{}\" target=\"_blank\" rel=\"noopener\">{}</a></p>", escape(license), content);
+        }
+        html += &links("Related", manifest.related());
+        html += &links("See also", manifest.see_also());
+        html += &links("Download", manifest.rendering());
+        html
+    }
+
+    /// Canvasごとの情報のHTML(descriptionもmetadataも無ければNone)
+    pub fn render_canvas(canvas: &Canvas) -> Option<String> {
+        if canvas.description().is_none() && canvas.metadata().is_empty() {
+            return None;
+        }
+        let mut html = format!("<h6>{}</h6>", escape(canvas.label()));
+        if let Some(description) = canvas.description() {
+            html += &format!("<p class=\"metadata-description\">{}</p>", label(description));
+        }
+        html += &metadata(canvas.metadata());
+        Some(html)
+    }
+
+    /// Manifestと表示中のCanvasの情報
+    pub struct MetadataView {
+        element: Element,
+        /// Canvasの情報を表示する要素
+        canvas: Option<Element>,
+        /// Canvasの@id -> 表示するHTML
+        canvases: HashMap<String, String>,
+        /// 画像の番号 -> Canvasの@id
+        images: Vec<String>,
+    }
+
+    impl MetadataView {
+        /// Manifest全体の情報を表示する
+        pub fn set_manifest(&mut self, manifest: &Manifest) {
+            self.element.set_inner_html(&render_manifest(manifest));
+            self.canvases = manifest.canvas_list().into_iter()
+                .filter_map(|canvas| Some((canvas.id().to_string(), render_canvas(canvas)?)))
+                .collect();
+
+            let document = web_sys::window().unwrap().document().unwrap();
+            self.canvas = document.create_element("div").ok();
+            if let Some(canvas) = &self.canvas {
+                let _ = canvas.class_list().add_1("metadata-canvas");
+                let _ = self.element.append_child(canvas);
+            }
+        }
+    }
+
+    impl View for MetadataView {
+        fn new(element: Element) -> Self {
+            Self { element, canvas: None, canvases: HashMap::new(), images: Vec::new() }
+        }
+
+        fn initialize(&mut self, viewer_images: &Vec<ViewerImage>) {
+            self.images = viewer_images.iter().map(|image| image.canvas.clone()).collect();
+        }
+
+        fn reset(&mut self) {
+            self.element.set_inner_html("");
+            self.canvas = None;
+            self.canvases.clear();
+            self.images.clear();
+        }
+
+        /// 表示中のCanvasの情報に切り替える
+        fn set_current(&mut self, current: Range<usize>) {
+            let canvas = match &self.canvas {
+                Some(canvas) => canvas,
+                None => return,
+            };
+            let html = self.images.get(current.start)
+                .and_then(|id| self.canvases.get(id))
+                .map(String::as_str)
+                .unwrap_or("");
+            canvas.set_inner_html(html);
+        }
+    }
+}
+
+pub mod search_view {
+    use super::*;
+    use crate::search::{SearchQuery, SearchResult, SearchResults};
+
+    /// 検索結果が選ばれたときにsearch-viewの要素から送るイベント(detailはSearchResult)
+    pub const OPEN_EVENT: &str = "result-open";
+    /// ページが選ばれたときに送るイベント(detailはそのページを取得するSearchQuery)
+    pub const PAGE_EVENT: &str = "result-page";
+    /// ページ送りに並べるページ数
+    const PAGE_WINDOW: u32 = 5;
+
+    /// ページ送りに並べるページ番号(1始まり)
+    /// 表示中のページをなるべく中央にする
+    pub fn page_numbers(current: u32, count: u32, width: u32) -> Range<u32> {
+        if count == 0 {
+            return 1..1;
+        }
+        let last = (current.saturating_sub(width / 2).max(1) + width - 1).min(count);
+        let first = (last + 1).saturating_sub(width).max(1);
+        first..last + 1
+    }
+
+    struct State {
+        results: Vec<SearchResult>,
+        /// 表示中の結果を取得したクエリ
+        query: Option<SearchQuery>,
+        /// 次のページのクエリ
+        next: Option<SearchQuery>,
+    }
+
+    /// Manifestの検索結果
+    #[wasm_bindgen]
+    pub struct SearchView {
+        element: Element,
+        state: Rc<RefCell<State>>,
+        onclick: Option<Closure<dyn FnMut(web_sys::Event)>>,
+    }
+
+    impl SearchView {
+        fn card(index: usize, result: &SearchResult) -> String {
+            // thumbnailが無ければアイコンを表示する
+            let image = match result.thumbnail() {
+                Some(thumbnail) => format!("<img src=\"{}\" alt=\"\">", escape(&thumbnail)),
+                None => "<div class=\"search-card-placeholder\"><i class=\"material-icons large\">collections</i></div>".to_string(),
+            };
+            format!("<div class=\"card search-card\" data-result=\"{}\">\
+                     <div class=\"card-image\">{}<span class=\"card-title\">{}</span>\
+                     <a class=\"btn-floating halfway-fab waves-effect waves-light btn-large\"><i class=\"material-icons\">launch</i></a></div>\
+                     <div class=\"card-content\">{}</div></div>",
+                    index, image, escape(&result.title()), escape(&result.description()))
+        }
+
+        /// startによるページ送り
+        fn pagination(query: &SearchQuery, total: u32) -> String {
+            let rows = (query.rows() as u32).max(1);
+            let current = (query.start().max(1) - 1) / rows + 1;
+            let count = (total + rows - 1) / rows;
+            if count <= 1 {
+                return String::new();
+            }
+            let item = |page: u32, content: &str, class: &str| {
+                if class.is_empty() {
+                    format!("<li class=\"waves-effect\"><a data-start=\"{}\">{}</a></li>", (page - 1) * rows + 1, content)
+                } else {
+                    format!("<li class=\"{}\"><a>{}</a></li>", class, content)
+                }
+            };
+            let mut html = item(current - 1, "<i class=\"material-icons\">chevron_left</i>", if current == 1 { "disabled" } else { "" });
+            for page in page_numbers(current, count, PAGE_WINDOW) {
+                html += &item(page, &page.to_string(), if page == current { "active" } else { "" });
+            }
+            html += &item(current + 1, "<i class=\"material-icons\">chevron_right</i>", if current == count { "disabled" } else { "" });
+            format!("<ul class=\"pagination center\">{}</ul>", html)
+        }
+
+        fn message(&self, icon: &str, message: &str) {
+            self.element.set_inner_html(&format!(
+                "<p class=\"search-view-message center\"><i class=\"material-icons left\">{}</i>{}</p>", icon, escape(message)));
+        }
+
+        /// 結果とページの選択を受け取る
+        fn listen(&mut self) {
+            let state = self.state.clone();
+            let element = self.element.clone();
+            let onclick = Closure::wrap(Box::new(move |event: web_sys::Event| {
+                let target = match event.target().and_then(|t| t.dyn_into::<Element>().ok()) {
+                    Some(target) => target,
+                    None => return,
+                };
+                let state = state.borrow();
+                let (name, detail) = if let Ok(Some(card)) = target.closest("[data-result]") {
+                    let result = card.get_attribute("data-result")
+                        .and_then(|i| i.parse::<usize>().ok())
+                        .and_then(|i| state.results.get(i));
+                    match result {
+                        Some(result) => (OPEN_EVENT, JsValue::from(result.clone())),
+                        None => return,
+                    }
+                } else if let Ok(Some(link)) = target.closest("[data-start]") {
+                    let start = link.get_attribute("data-start").and_then(|s| s.parse::<u32>().ok());
+                    match (start, &state.query) {
+                        (Some(start), Some(query)) => {
+                            let mut query = query.clone();
+                            query.set_start(start);
+                            (PAGE_EVENT, JsValue::from(query))
+                        }
+                        _ => return,
+                    }
+                } else if let Ok(Some(_)) = target.closest("[data-next]") {
+                    match &state.next {
+                        Some(next) => (PAGE_EVENT, JsValue::from(next.clone())),
+                        None => return,
+                    }
+                } else {
+                    return;
+                };
+                let mut init = web_sys::CustomEventInit::new();
+                init.bubbles(true);
+                init.detail(&detail);
+                match web_sys::CustomEvent::new_with_event_init_dict(name, &init) {
+                    Ok(event) => {
+                        let _ = element.dispatch_event(&event);
+                    }
+                    Err(_) => crate::viewer::log("Cannot create search view event"),
+                }
+            }) as Box<dyn FnMut(web_sys::Event)>);
+            let _ = self.element.add_event_listener_with_callback("click", onclick.as_ref().unchecked_ref());
+            self.onclick = Some(onclick);
+        }
+    }
+
+    impl View for SearchView {
+        fn new(element: Element) -> Self {
+            let state = State { results: Vec::new(), query: None, next: None };
+            let mut view = Self { element, state: Rc::new(RefCell::new(state)), onclick: None };
+            view.listen();
+            view
+        }
+
+        /// 何もしない(検索結果はManifestの画像と関係しない)
+        fn initialize(&mut self, _viewer_images: &Vec<ViewerImage>) {}
+
+        fn reset(&mut self) {
+            let mut state = self.state.borrow_mut();
+            state.results.clear();
+            state.query = None;
+            state.next = None;
+            self.element.set_inner_html("");
+        }
+
+        /// 何もしない
+        fn set_current(&mut self, _current: Range<usize>) {}
+    }
+
+    #[wasm_bindgen]
+    impl SearchView {
+        #[wasm_bindgen(constructor)]
+        pub fn create(element: Element) -> SearchView {
+            <SearchView as View>::new(element)
+        }
+
+        /// 検索中の表示にする
+        pub fn loading(&mut self) {
+            self.reset();
+            self.element.set_inner_html(
+                "<div class=\"search-view-message center\"><div class=\"preloader-wrapper big active\">\
+                 <div class=\"spinner-layer spinner-blue-only\"><div class=\"circle-clipper left\"><div class=\"circle\"></div></div>\
+                 <div class=\"gap-patch\"><div class=\"circle\"></div></div>\
+                 <div class=\"circle-clipper right\"><div class=\"circle\"></div></div></div></div></div>");
+        }
+
+        /// queryで取得した検索結果を表示する
+        pub fn results(&mut self, results: &SearchResults, query: &SearchQuery) {
+            self.reset();
+            if results.len() == 0 {
+                self.message("search", "No results");
+                return;
+            }
+            let cards: String = (0..results.len())
+                .filter_map(|i| results.get(i))
+                .enumerate()
+                .map(|(i, result)| Self::card(i, &result))
+                .collect();
+            let pagination = if query.cursor().is_some() {
+                match query.next_page(results) {
+                    Some(_) => "<ul class=\"pagination center\"><li class=\"waves-effect\"><a data-next>More<i class=\"material-icons right\">expand_more</i></a></li></ul>".to_string(),
+                    None => String::new(),
+                }
+            } else {
+                Self::pagination(query, results.total())
+            };
+            self.element.set_inner_html(&format!("<div class=\"search-results\">{}</div>{}", cards, pagination));
+
+            let mut state = self.state.borrow_mut();
+            state.results = (0..results.len()).filter_map(|i| results.get(i)).collect();
+            state.next = query.next_page(results);
+            state.query = Some(query.clone());
+        }
+
+        /// 検索に失敗したことを表示する
+        pub fn error(&mut self, message: String) {
+            self.reset();
+            self.message("error", &message);
+        }
+    }
+}
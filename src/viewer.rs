@@ -3,9 +3,16 @@ use wasm_bindgen::JsCast;
 
 use web_sys::{Element, HtmlImageElement, HtmlCanvasElement, CanvasRenderingContext2d, MouseEvent, Node};
 use js_sys::Promise;
+use futures::{future, Future};
+use wasm_bindgen_futures::{JsFuture, future_to_promise};
 
-use crate::iiif_manifest::{Manifest, Label};
-use crate::view::{View, list_view::ListView, icon_view::IconView};
+use crate::iiif_manifest::Manifest;
+use crate::view::{View, list_view::ListView, icon_view::IconView, metadata_view::MetadataView, filmstrip_view::FilmstripView};
+use crate::content_search::{self, ContentSearchHit};
+use crate::index;
+use crate::compare::Comparison;
+use crate::adjustment::{Adjustment, ServerSide};
+use crate::search::{discovery, SearchResult};
 
 #[wasm_bindgen]
 extern "C" {
@@ -13,13 +20,29 @@ extern "C" {
     pub fn log(s: &str);
 }
 
+/// 画像の倍率の範囲
+const MIN_ZOOM: f64 = 0.2;
+const MAX_ZOOM: f64 = 8.0;
+
 #[wasm_bindgen]
 struct Viewer {
     canvas: Canvas,
     list_view: ListView,
     icon_view: IconView,
+    /// Manifestの情報(無くてもよい)
+    metadata_view: Option<MetadataView>,
+    /// 画像の下の一覧とscrubber(無くてもよい)
+    filmstrip_view: Option<FilmstripView>,
     images: Vec<ViewerImage>,
     manifest: Option<Manifest>,
+    /// Content Searchの検索結果
+    hits: Vec<ContentSearchHit>,
+    /// 強調する検索結果(そのページを表示したときに強調する)
+    hit: Option<usize>,
+    /// 検索結果を強調する要素(画像と同じtransformをかける)
+    highlight: Option<Element>,
+    /// すべての画像の見え方の調整(画像ごとの調整が無いとき)
+    adjustment: Adjustment,
     pub index: usize,
 }
 
@@ -29,7 +52,7 @@ impl Viewer {
     /// Viewerのコンストラクタ
     pub fn new(canvas: Element, list_view: Element, icon_view: Element) -> Self {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
-        Self { canvas: Canvas::new(canvas), list_view: ListView::new(list_view), icon_view: IconView::new(icon_view), images: Vec::new(), manifest: None, index: 0 }
+        Self { canvas: Canvas::new(canvas), list_view: ListView::new(list_view), icon_view: IconView::new(icon_view), metadata_view: None, filmstrip_view: None, images: Vec::new(), manifest: None, hits: Vec::new(), hit: None, highlight: None, adjustment: Adjustment::default(), index: 0 }
     }
 
     #[wasm_bindgen]
@@ -44,15 +67,37 @@ impl Viewer {
             }
         };
 
+        // 前のManifestの表示を消す
+        self.list_view.reset();
+        self.icon_view.reset();
+        self.clear_highlight();
+        self.canvas.clear();
+        self.hits.clear();
+        self.index = 0;
+
         // push images
         let images = manifest.get_viewer_images();
         // set list_view
         self.list_view.initialize(&images);
         // set icon_view
         self.icon_view.initialize(&images);
+        // set metadata_view
+        if let Some(metadata_view) = &mut self.metadata_view {
+            metadata_view.reset();
+            metadata_view.set_manifest(&manifest);
+            metadata_view.initialize(&images);
+        }
+        // set filmstrip_view
+        if let Some(filmstrip_view) = &mut self.filmstrip_view {
+            filmstrip_view.reset();
+            filmstrip_view.set_right_to_left(manifest.right_to_left());
+            filmstrip_view.initialize(&images);
+        }
 
         // set images
         self.images = images;
+        // add to index
+        index::add_manifest(&manifest);
         // set manifest
         self.manifest = Some(manifest);
 
@@ -90,13 +135,25 @@ impl Viewer {
     /// イメージを表示する
     pub fn show(&mut self, index: usize) -> bool {
         if let Some(image) = self.images.get_mut(index) {
-            if !image.loading() {
-                image.load();
+            // Image APIで調整する画像が変わったときも読み込み直す
+            if !image.loading() || image.loaded_src != image.src(&self.adjustment) {
+                image.load(&self.adjustment);
                 return false;
             }
             if let Some(img) = &image.image {
                 self.index = index;
-                self.canvas.element.append_child(&Node::from(Element::from(img.clone())));
+                self.canvas.show(img);
+                self.render_highlight();
+                self.render_image();
+                // 表示中の画像をviewに知らせる
+                self.list_view.set_current(index..index + 1);
+                self.icon_view.set_current(index..index + 1);
+                if let Some(metadata_view) = &mut self.metadata_view {
+                    metadata_view.set_current(index..index + 1);
+                }
+                if let Some(filmstrip_view) = &mut self.filmstrip_view {
+                    filmstrip_view.set_current(index..index + 1);
+                }
                 return true;
             }
             return false;
@@ -104,21 +161,6 @@ impl Viewer {
         true
     }
 
-    #[wasm_bindgen]
-    /// イメージをsrcから表示する
-    pub fn get_index_by_src(&mut self, src: String) -> usize {
-        let images = &self.images;
-        let mut index = self.index;
-        for i in 0..images.len() {
-            let image = &images[i].src;
-            if image == &src {
-                index = i;
-                break;
-            }
-        }
-        index
-    }
-
     #[wasm_bindgen]
     /// 次のイメージを表示する
     pub fn next(&mut self) -> bool {
@@ -172,6 +214,47 @@ impl Viewer {
         self.canvas.mousedown = None;
     }
 
+    /// 表示中の画像と、otherのindex番目の画像を重ねて比べる(otherは自分でもよい)
+    /// otherの画像を読み込み始めていなければNone
+    pub fn compare(&self, other: &Viewer, index: usize) -> Option<Comparison> {
+        let base = self.images.get(self.index)?.image.clone()?;
+        let other = other.images.get(index)?.image.clone()?;
+        Comparison::new(&self.canvas.element, base, other)
+    }
+
+    /// 表示中の画像の位置
+    pub fn position(&self) -> Option<Position> {
+        let image = self.images.get(self.index)?;
+        Some(Position { x: image.position_x, y: image.position_y })
+    }
+
+    /// 表示中の画像の倍率
+    pub fn zoom_level(&self) -> f64 {
+        self.images.get(self.index).map_or(1.0, |image| image.zoom)
+    }
+
+    /// 表示中の画像をfactor倍に拡大縮小し、新しい倍率を返す
+    pub fn zoom(&mut self, factor: f64) -> f64 {
+        match self.images.get_mut(self.index) {
+            Some(image) => {
+                image.zoom = (image.zoom * factor).max(MIN_ZOOM).min(MAX_ZOOM);
+                image.zoom
+            }
+            None => 1.0,
+        }
+    }
+
+    /// 表示中の画像の位置と倍率を設定する(同期など)
+    pub fn set_transform(&mut self, x: f64, y: f64, zoom: f64) {
+        if let Some(image) = self.images.get_mut(self.index) {
+            image.position_x = x;
+            image.position_y = y;
+            image.original_x = x;
+            image.original_y = y;
+            image.zoom = zoom.max(MIN_ZOOM).min(MAX_ZOOM);
+        }
+    }
+
     /// canvasのelementを取得する
     fn canvas_elem(&self) -> &Element {
         &self.canvas.element
@@ -197,17 +280,45 @@ impl Viewer {
     #[wasm_bindgen]
     pub fn label(&self) -> String {
         match &self.manifest {
-            Some(m) => match &m.label {
-                Label::String(s) => s,
-                Label::Vec(vec) => match vec.get(0) {
-                    Some(ec) => &ec.value,
-                    None => "None",
-                },
-            },
+            Some(m) => m.label.texts().into_iter().next().unwrap_or("None"),
             None => "None",
         }.to_string()
     }
 
+    /// ListView, IconView, FilmstripViewを表示領域に合わせて作り直し、表示中の画像までスクロールする
+    /// (表示/非表示の切り替え後など)
+    pub fn render_views(&self) {
+        self.list_view.reveal();
+        self.icon_view.reveal();
+        if let Some(filmstrip_view) = &self.filmstrip_view {
+            filmstrip_view.reveal();
+        }
+    }
+
+    /// Manifestの情報を表示する要素を設定する
+    /// Manifestを読み込み済みならすぐに表示する
+    pub fn set_metadata_view(&mut self, element: Element) {
+        let mut metadata_view = MetadataView::new(element);
+        if let Some(manifest) = &self.manifest {
+            metadata_view.set_manifest(manifest);
+            metadata_view.initialize(&self.images);
+            metadata_view.set_current(self.index..self.index + 1);
+        }
+        self.metadata_view = Some(metadata_view);
+    }
+
+    /// 画像の下の一覧とscrubberを表示する要素を設定する
+    /// Manifestを読み込み済みならすぐに表示する
+    pub fn set_filmstrip_view(&mut self, element: Element) {
+        let mut filmstrip_view = FilmstripView::new(element);
+        if let Some(manifest) = &self.manifest {
+            filmstrip_view.set_right_to_left(manifest.right_to_left());
+            filmstrip_view.initialize(&self.images);
+            filmstrip_view.set_current(self.index..self.index + 1);
+        }
+        self.filmstrip_view = Some(filmstrip_view);
+    }
+
     pub fn image_label(&self) -> String {
         if let Some(img) = self.images.get(self.index) {
             img.label.clone()
@@ -215,6 +326,317 @@ impl Viewer {
             String::new()
         }
     }
+
+    /// 表示中の画像のImage APIのservice(@id)
+    pub fn image_service(&self) -> Option<String> {
+        self.images.get(self.index)?.service.clone()
+    }
+
+    /// 表示中の画像の元のURL(Image APIで調整する前のもの)
+    pub fn image_src(&self) -> Option<String> {
+        Some(self.images.get(self.index)?.src.clone())
+    }
+}
+
+#[wasm_bindgen]
+/// 検索結果から開く
+impl Viewer {
+    /// 検索結果のManifestを読み込んだViewerを作る(Promise<Viewer>)
+    /// ManifestのURLが分からなければ検索結果のページから探す
+    /// Manifestが無い、または読み込めなければエラー(reject)
+    pub fn open_search_result(canvas: Element, list_view: Element, icon_view: Element, result: &SearchResult) -> Promise {
+        let manifest_url: Box<dyn Future<Item = String, Error = JsValue>> = match result.resolve_manifest() {
+            Some(url) => Box::new(future::ok(url)),
+            None => {
+                let page = result.url();
+                let error = JsValue::from_str(&result.no_manifest().to_string());
+                Box::new(fetch_text(&page).then(move |html| {
+                    html.ok()
+                        .and_then(|html| discovery::discover_manifest(&html, &page))
+                        .ok_or(error)
+                }))
+            }
+        };
+
+        let future = manifest_url
+            .and_then(|url| fetch_text(&url))
+            .and_then(move |json| {
+                let mut viewer = Viewer::new(canvas, list_view, icon_view);
+                if viewer.set_manifest(json) {
+                    Ok(JsValue::from(viewer))
+                } else {
+                    Err(JsValue::from_str("Cannot read manifest"))
+                }
+            });
+        future_to_promise(future)
+    }
+}
+
+#[wasm_bindgen]
+/// 画像の見え方の調整に関する実装
+/// page_onlyなら表示中の画像だけを調整する
+/// 変更後はshowで表示し直す(Image APIで調整する画像は読み込み直す)
+impl Viewer {
+    /// 明るさ、コントラスト、彩度、反転、グレースケール(%)
+    pub fn set_filter(&mut self, brightness: f64, contrast: f64, saturation: f64, invert: f64, grayscale: f64, page_only: bool) {
+        self.adjust(page_only, |adjustment| {
+            adjustment.brightness = brightness;
+            adjustment.contrast = contrast;
+            adjustment.saturation = saturation;
+            adjustment.invert = invert;
+            adjustment.grayscale = grayscale;
+        });
+    }
+
+    /// 90度回転する
+    pub fn rotate(&mut self, clockwise: bool, page_only: bool) {
+        self.adjust(page_only, |adjustment| adjustment.rotate(clockwise));
+    }
+
+    /// 左右反転を切り替える
+    pub fn mirror(&mut self, page_only: bool) {
+        self.adjust(page_only, |adjustment| adjustment.mirror = !adjustment.mirror);
+    }
+
+    /// すべての調整を元に戻す
+    pub fn reset_adjustment(&mut self) {
+        self.adjustment = Adjustment::default();
+        for image in &mut self.images {
+            image.adjustment = None;
+        }
+        self.render_image();
+    }
+
+    /// 表示中の画像の調整(JSON)
+    /// `{"brightness": 100.0, "contrast": 100.0, ..., "rotation": 90, "mirror": false}`
+    pub fn adjustment(&self) -> String {
+        let adjustment = match self.images.get(self.index) {
+            Some(image) => image.adjustment(&self.adjustment),
+            None => &self.adjustment,
+        };
+        serde_json::to_string(adjustment).unwrap_or(String::new())
+    }
+
+    /// 表示中の画像と検索結果の強調に位置、倍率、調整を反映する
+    pub fn render(&self) {
+        self.render_image();
+    }
+
+    /// 表示中の画像のcssのtransform(位置、倍率、回転、反転)
+    pub fn transform(&self) -> String {
+        match self.images.get(self.index) {
+            Some(image) => {
+                let adjustment = image.adjustment(&self.adjustment);
+                format!("translate({}px, {}px) scale({}) {}", image.position_x, image.position_y, image.zoom, adjustment.transform(image.applied()))
+            }
+            None => String::new(),
+        }
+    }
+}
+
+impl Viewer {
+    fn adjust<F: FnOnce(&mut Adjustment)>(&mut self, page_only: bool, f: F) {
+        if page_only {
+            let global = self.adjustment.clone();
+            if let Some(image) = self.images.get_mut(self.index) {
+                let mut adjustment = image.adjustment.take().unwrap_or(global);
+                f(&mut adjustment);
+                image.adjustment = Some(adjustment);
+            }
+        } else {
+            f(&mut self.adjustment);
+        }
+        self.render_image();
+    }
+
+    /// 表示中の画像に調整を反映する
+    fn render_image(&self) {
+        let image = match self.images.get(self.index) {
+            Some(image) => image,
+            None => return,
+        };
+        let img = match &image.image {
+            Some(img) => img,
+            None => return,
+        };
+        // 新しいURLの読み込みが終わるまでは前の画像に合わせる
+        let adjustment = image.adjustment(&self.adjustment);
+        let server = image.applied();
+        let style = img.style();
+        let _ = style.set_property("filter", &adjustment.filter(server));
+        let _ = style.set_property("transform", &self.transform());
+        self.place_highlight(img, server.rotate_region(self.hit_region()));
+    }
+}
+
+/// URLの内容を文字列として取得する
+fn fetch_text(url: &str) -> impl Future<Item = String, Error = JsValue> {
+    let window = web_sys::window().expect("no global `window` exists");
+    let url = url.to_string();
+    JsFuture::from(window.fetch_with_str(&url))
+        .and_then(move |response| {
+            let response: web_sys::Response = response.dyn_into()?;
+            if !response.ok() {
+                return Err(JsValue::from_str(&format!("Cannot fetch {}: {}", url, response.status())));
+            }
+            response.text()
+        })
+        .and_then(JsFuture::from)
+        .map(|text| text.as_string().unwrap_or_default())
+}
+
+#[wasm_bindgen]
+/// IIIF Content Searchに関する実装
+impl Viewer {
+    /// ManifestがContent Searchに対応しているか否か
+    pub fn has_content_search(&self) -> bool {
+        self.manifest.as_ref().and_then(Manifest::search_service).is_some()
+    }
+
+    /// Manifest内を検索するURL
+    pub fn content_search_url(&self, q: String) -> Option<String> {
+        let service = self.manifest.as_ref()?.search_service()?;
+        Some(content_search::search_url(&service.id, &q))
+    }
+
+    /// 検索語を自動補完するURL
+    pub fn autocomplete_url(&self, q: String) -> Option<String> {
+        let service = self.manifest.as_ref()?.search_service()?.autocomplete()?;
+        Some(content_search::search_url(&service.id, &q))
+    }
+
+    /// 自動補完の結果から候補を取り出す(JSON配列)
+    /// `[{"match": "fuji", "url": "...", "count": 3}, ...]`
+    pub fn autocomplete_terms(&self, json: String) -> String {
+        match content_search::parse_terms(&json) {
+            Ok(terms) => serde_json::to_string(&terms).unwrap_or(String::new()),
+            Err(_) => {
+                log("Cannot read autocomplete terms");
+                "[]".to_string()
+            }
+        }
+    }
+
+    /// 検索結果を読み込む
+    pub fn set_content_search_results(&mut self, json: String) -> bool {
+        self.clear_highlight();
+        match content_search::parse_hits(&json) {
+            Ok(hits) => {
+                self.hits = hits;
+                true
+            }
+            Err(_) => {
+                log("Cannot read content search results");
+                self.hits = Vec::new();
+                false
+            }
+        }
+    }
+
+    /// 検索結果の一覧(JSON配列)
+    /// `[{"index": 2, "canvas": "...", "xywh": [x, y, w, h], "before": "...", "match": "...", "after": "..."}, ...]`
+    pub fn content_search_hits(&self) -> String {
+        let hits: Vec<_> = self.hits.iter()
+            .map(|hit| {
+                let mut value = serde_json::to_value(hit).unwrap_or_default();
+                value["index"] = serde_json::json!(self.index_by_canvas(&hit.canvas));
+                value
+            })
+            .collect();
+        serde_json::to_string(&hits).unwrap_or(String::new())
+    }
+
+    /// i番目の検索結果のページを表示し、範囲を強調する
+    /// 画像の読み込みが終わっていなければfalse
+    pub fn show_hit(&mut self, i: usize) -> bool {
+        let hit = match self.hits.get(i) {
+            Some(hit) => hit.clone(),
+            None => return false,
+        };
+        let index = match self.index_by_canvas(&hit.canvas) {
+            Some(index) => index,
+            None => return false,
+        };
+        // 読み込み中なら、読み込んだ後にshowで強調する
+        self.clear_highlight();
+        self.hit = Some(i);
+        self.show(index)
+    }
+
+    /// 強調を消す
+    pub fn clear_highlight(&mut self) {
+        self.hit = None;
+        if let Some(highlight) = self.highlight.take() {
+            highlight.remove();
+        }
+    }
+}
+
+impl Viewer {
+    /// Canvasの@idから画像のindexを探す
+    fn index_by_canvas(&self, canvas: &str) -> Option<usize> {
+        self.images.iter().position(|image| image.canvas == canvas)
+    }
+
+    /// 表示中のページにある、強調する検索結果のCanvas上の範囲(割合)
+    fn hit_region(&self) -> (f64, f64, f64, f64) {
+        let image = match self.images.get(self.index) {
+            Some(image) if image.canvas_width > 0 && image.canvas_height > 0 => image,
+            _ => return (0.0, 0.0, 0.0, 0.0),
+        };
+        match self.hit.and_then(|i| self.hits.get(i)).and_then(|hit| hit.xywh) {
+            Some((x, y, w, h)) => {
+                let (width, height) = (image.canvas_width as f64, image.canvas_height as f64);
+                (x as f64 / width, y as f64 / height, w as f64 / width, h as f64 / height)
+            }
+            None => (0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    /// 表示中のページに検索結果があれば強調する要素を作る
+    fn render_highlight(&mut self) {
+        if let Some(highlight) = self.highlight.take() {
+            highlight.remove();
+        }
+        let on_page = self.hit
+            .and_then(|i| self.hits.get(i))
+            .filter(|hit| hit.xywh.is_some())
+            .map_or(false, |hit| self.index_by_canvas(&hit.canvas) == Some(self.index));
+        if !on_page {
+            return;
+        }
+
+        let document = web_sys::window().unwrap().document().unwrap();
+        let (layer, hit) = match (document.create_element("div"), document.create_element("div")) {
+            (Ok(layer), Ok(hit)) => (layer, hit),
+            _ => return,
+        };
+        let _ = layer.class_list().add_1("search-layer");
+        let _ = hit.class_list().add_1("search-hit");
+        let _ = layer.append_child(&hit);
+        // viewer-canvasのappendChildは画像しか受け付けないので、insertAdjacentElementで追加する
+        let _ = self.canvas.element.insert_adjacent_element("beforeend", &layer);
+        self.highlight = Some(layer);
+    }
+
+    /// 強調する要素を画像に重ねる
+    /// 画像と同じ大きさ、transformにして、範囲は割合で示す
+    fn place_highlight(&self, img: &HtmlImageElement, (x, y, w, h): (f64, f64, f64, f64)) {
+        let layer = match &self.highlight {
+            Some(layer) => layer,
+            None => return,
+        };
+        let _ = layer.set_attribute("style", &format!(
+            "left: {}px; top: {}px; width: {}px; height: {}px; transform: {};",
+            img.offset_left(), img.offset_top(), img.offset_width(), img.offset_height(), self.transform(),
+        ));
+        if let Some(hit) = layer.first_element_child() {
+            let _ = hit.set_attribute("style", &format!(
+                "left: {}%; top: {}%; width: {}%; height: {}%;",
+                x * 100.0, y * 100.0, w * 100.0, h * 100.0,
+            ));
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -244,7 +666,7 @@ impl Viewer {
             if image.loaded() {
                 log(&format!("viewer.images[{}] is loaded.", index));
             } else {
-                image.load();
+                image.load(&self.adjustment);
             }
         }
     }
@@ -274,48 +696,96 @@ impl Viewer {
 pub struct Canvas {
     pub element: Element,
     pub mousedown: Option<(f64, f64)>,
+    /// 表示中の画像
+    image: Option<HtmlImageElement>,
 }
 
 impl Canvas {
     pub fn new(element: Element) -> Self {
-        Self { element, mousedown: None }
+        Self { element, mousedown: None, image: None }
+    }
+
+    /// 表示中の画像を置き換える
+    pub fn show(&mut self, image: &HtmlImageElement) {
+        if self.image.as_ref() == Some(image) {
+            return;
+        }
+        self.clear();
+        if self.element.append_child(&Node::from(Element::from(image.clone()))).is_ok() {
+            self.image = Some(image.clone());
+        }
+    }
+
+    /// 表示中の画像を消す
+    pub fn clear(&mut self) {
+        if let Some(image) = self.image.take() {
+            image.remove();
+        }
+        self.mousedown = None;
     }
 }
 
 pub struct ViewerImage {
     pub image: Option<HtmlImageElement>,
-    pub thumbnail: Option<HtmlImageElement>,
+    /// thumbnailのURL(IconViewで見えるようになってから読み込む)
+    pub thumbnail: Option<String>,
     pub label: String,
     pub src: String,
+    /// 画像を含むCanvasの@id
+    pub canvas: String,
+    /// Canvasの大きさ
+    pub canvas_width: u32,
+    pub canvas_height: u32,
     pub position_x: f64,
     pub position_y: f64,
     pub original_x: f64,
     pub original_y: f64,
     pub zoom: f64,
+    /// Image APIのservice(@id)
+    pub service: Option<String>,
+    /// Image APIのcompliance level
+    pub service_level: Option<u8>,
+    /// この画像だけの見え方の調整
+    pub adjustment: Option<Adjustment>,
+    /// 読み込んだURL(Image APIで調整したもの)
+    pub loaded_src: String,
 }
 
 impl ViewerImage {
     pub fn new(src: &str, label: &str, thumbnail: Option<&str>) -> Self {
         let src = src.to_string();
         let label = label.to_string();
-        let thumbnail = thumbnail.map(|string| {
-            let image = HtmlImageElement::new().unwrap();
-            image.set_src(string);
-            image
-        });
+        let thumbnail = thumbnail.map(str::to_string);
         Self {
             image: None,
             thumbnail,
             src,
             label,
+            canvas: String::new(),
+            canvas_width: 0,
+            canvas_height: 0,
             position_x: 0.0,
             position_y: 0.0,
             original_x: 0.0,
             original_y: 0.0,
             zoom: 1.0,
+            service: None,
+            service_level: None,
+            adjustment: None,
+            loaded_src: String::new(),
         }
     }
 
+    /// この画像に使う調整(無ければglobal)
+    pub fn adjustment<'a>(&'a self, global: &'a Adjustment) -> &'a Adjustment {
+        self.adjustment.as_ref().unwrap_or(global)
+    }
+
+    /// 調整したときに読み込むURL
+    pub fn src(&self, global: &Adjustment) -> String {
+        self.adjustment(global).src(&self.src, self.service_level)
+    }
+
     /// 読み込みを試みたか否か
     pub fn loading(&self) -> bool {
         self.image.is_some()
@@ -329,12 +799,29 @@ impl ViewerImage {
     }
 
     /// 読み込む
-    pub fn load(&mut self) {
-        let image = HtmlImageElement::new().unwrap();
-        image.set_cross_origin(Some("Anonymous"));
-        image.set_src(&self.src);
+    /// 読み込み直すときは同じ要素のsrcを変える(JSのloadイベントを残すため)
+    pub fn load(&mut self, global: &Adjustment) {
+        let image = match self.image.take() {
+            Some(image) => image,
+            None => {
+                let image = HtmlImageElement::new().unwrap();
+                image.set_cross_origin(Some("Anonymous"));
+                image
+            }
+        };
+        self.loaded_src = self.src(global);
+        image.set_src(&self.loaded_src);
         self.image = Some(image);
     }
+
+    /// 表示中の画像にImage APIが行った調整
+    /// srcを変えても読み込みが終わるまでは前の画像が表示される(currentSrcも前のまま)
+    pub fn applied(&self) -> ServerSide {
+        match &self.image {
+            Some(image) => ServerSide::applied(&self.src, &image.current_src()),
+            None => ServerSide::default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize, Default)]